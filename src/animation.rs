@@ -0,0 +1,225 @@
+use cgmath::Vector3;
+use function::Function;
+use function_ir::Node;
+use interval::{hull, Interval};
+use mesh_tree::{BoundingBox, Geometry, MeshTree};
+use mesh_tree_error::MeshTreeResult;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use std::path::PathBuf;
+
+// An expression in x, y, z and t, with t bound to an interval of times. Over
+// a single time it's the surface at that moment, over a range it bounds the
+// surface at every moment in the range.
+#[derive(Clone, Debug)]
+pub struct AtTime {
+    node: Node,
+    time: Interval,
+}
+
+impl AtTime {
+    pub fn new(node: Node, time: f32) -> AtTime {
+        AtTime::over(node, Interval::new(time, time))
+    }
+
+    pub fn over(node: Node, time: Interval) -> AtTime {
+        AtTime { node, time }
+    }
+
+    fn point_bindings(&self, x: f32, y: f32, z: f32) -> HashMap<char, f32> {
+        let mut bindings = HashMap::new();
+        bindings.insert('x', x);
+        bindings.insert('y', y);
+        bindings.insert('z', z);
+        bindings.insert('t', self.time.middle());
+        bindings
+    }
+
+    fn interval_bindings(&self, bindings: &HashMap<char, Interval>) -> HashMap<char, Interval> {
+        let mut bindings = bindings.clone();
+        bindings.insert('t', self.time);
+        bindings
+    }
+}
+
+impl Function for AtTime {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        self.node.evaluate(&self.point_bindings(x, y, z))
+    }
+
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        self.node.evaluate_intervals(&self.interval_bindings(bindings))
+    }
+
+    fn gradient(&self, x: f32, y: f32, z: f32) -> Vector3<f32> {
+        self.node.evaluate_gradient(&self.point_bindings(x, y, z)).1
+    }
+
+    fn evaluate_gradient_interval(
+        &self,
+        bindings: &HashMap<char, Interval>,
+    ) -> [Vec<Interval>; 3] {
+        self.node
+            .evaluate_gradient_intervals(&self.interval_bindings(bindings))
+            .1
+    }
+}
+
+// Meshes an expression at a sequence of times. The first `coarse_level`
+// levels are found once with t bound to the span of all the times, and every
+// frame refines from there. Fast moving surfaces cull little over the whole
+// span, so the coarse level should be lower for them.
+pub struct Animation {
+    node: Node,
+    bb: BoundingBox,
+    pub coarse_level: u32,
+    pub level: u32,
+}
+
+impl Animation {
+    pub fn new(node: Node, bb: BoundingBox, level: u32) -> Animation {
+        Animation {
+            node,
+            bb,
+            coarse_level: level / 2,
+            level,
+        }
+    }
+
+    pub fn frames(&self, times: &[f32]) -> MeshTreeResult<Vec<Geometry>> {
+        if times.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let span = hull(&times.iter().map(|&t| Interval::new(t, t)).collect::<Vec<_>>());
+        let mut coarse = MeshTree::new(Box::new(AtTime::over(self.node.clone(), span)), self.bb);
+        for _ in 0..self.coarse_level.min(self.level) {
+            coarse.next_level()?;
+        }
+
+        times
+            .iter()
+            .map(|&t| {
+                let mut tree = coarse.rebind(Box::new(AtTime::new(self.node.clone(), t)));
+                while tree.level < self.level {
+                    tree.next_level()?;
+                }
+                tree.generate_vertex_map();
+                tree.generate_triangle_set();
+                Ok(tree.geometry())
+            })
+            .collect()
+    }
+
+    // Writes a numbered OBJ file per frame, `<prefix>0000.obj` and so on,
+    // returning the paths written
+    pub fn export(&self, times: &[f32], prefix: &str) -> io::Result<Vec<PathBuf>> {
+        let frames = self
+            .frames(times)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+
+        let mut paths = Vec::new();
+        for (i, geometry) in frames.iter().enumerate() {
+            let path = PathBuf::from(format!("{}{:04}.obj", prefix, i));
+            let mut writer = BufWriter::new(File::create(&path)?);
+            geometry.write_obj(&mut writer)?;
+            paths.push(path);
+        }
+        Ok(paths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::parse_expression;
+    use std::env;
+    use std::fs;
+    use test_util::cell;
+
+    fn bb() -> BoundingBox {
+        cell((-1.5, 1.5), (-1.5, 1.5), (-1.5, 1.5))
+    }
+
+    // A sphere growing from radius 0.5 to 1 as t goes from 0 to 1
+    fn growing() -> Node {
+        let input: Vec<char> = "x^2 + y^2 + z^2 - (0.5 + 0.5 * t)^2".chars().collect();
+        *parse_expression(&input, 0).unwrap()
+    }
+
+    #[test]
+    fn binds_time() {
+        let f = AtTime::new(growing(), 1.0);
+        assert_similiar!(f.evaluate(1.0, 0.0, 0.0), 0.0);
+        assert_similiar!(f.gradient(1.0, 0.0, 0.0).x, 2.0);
+
+        // Over a span the bound covers every time in it
+        let span = AtTime::over(growing(), Interval::new(0.0, 1.0));
+        let point = BoundingBox {
+            x: Interval::new(0.75, 0.75),
+            y: Interval::new(0.0, 0.0),
+            z: Interval::new(0.0, 0.0),
+        };
+        let bound = hull(&span.evaluate_interval(&point.bindings()));
+        for i in 0..11 {
+            let v = AtTime::new(growing(), i as f32 / 10.0).evaluate(0.75, 0.0, 0.0);
+            assert!(v >= bound.min && v <= bound.max);
+        }
+    }
+
+    #[test]
+    fn frames_match_fresh_meshes() {
+        let times = [0.0, 0.5, 1.0];
+        let animation = Animation::new(growing(), bb(), 4);
+        let frames = animation.frames(&times).unwrap();
+        assert_eq!(frames.len(), 3);
+
+        for (geometry, &t) in frames.iter().zip(&times) {
+            let mut tree = MeshTree::new(Box::new(AtTime::new(growing(), t)), bb());
+            for _ in 0..4 {
+                tree.next_level().unwrap();
+            }
+            tree.generate_vertex_map();
+            tree.generate_triangle_set();
+            let fresh = tree.geometry();
+
+            assert_eq!(geometry.vertices, fresh.vertices);
+            assert_eq!(geometry.triangles, fresh.triangles);
+
+            let radius = 0.5 + 0.5 * t;
+            for v in &geometry.vertices {
+                assert!(((v.x * v.x + v.y * v.y + v.z * v.z).sqrt() - radius).abs() < 0.05);
+            }
+        }
+
+        // The sphere grows, so later frames have more vertices
+        assert!(frames[0].vertices.len() < frames[2].vertices.len());
+    }
+
+    #[test]
+    fn export_numbered_files() {
+        let directory =
+            env::temp_dir().join(format!("implicit_animation_{}", ::std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let prefix = directory.join("frame_");
+
+        let animation = Animation::new(growing(), bb(), 3);
+        let paths = animation
+            .export(&[0.0, 1.0], prefix.to_str().unwrap())
+            .unwrap();
+        assert_eq!(
+            paths,
+            vec![
+                directory.join("frame_0000.obj"),
+                directory.join("frame_0001.obj"),
+            ]
+        );
+
+        let obj = fs::read_to_string(&paths[1]).unwrap();
+        assert!(obj.lines().any(|l| l.starts_with("f ")));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,364 @@
+use cgmath::Vector3;
+use function::Function;
+use interval::{combine_intervals, hull, Interval};
+use std::collections::HashMap;
+
+// Constructive solid geometry on anything implementing Function, treating
+// negative as inside. Min, max and the smooth min are all non-decreasing in
+// both arguments, so applying them to the bounds of each side gives the exact
+// range over a box.
+
+// Each combinator takes the gradient of whichever side is active. Over a box
+// that straddles the switch it is somewhere between the two, which the hull
+// of both covers.
+fn select_gradient<GA, GB>(only_a: bool, only_b: bool, ga: GA, gb: GB) -> [Vec<Interval>; 3]
+where
+    GA: FnOnce() -> [Vec<Interval>; 3],
+    GB: FnOnce() -> [Vec<Interval>; 3],
+{
+    if only_a {
+        return ga();
+    }
+    if only_b {
+        return gb();
+    }
+
+    let (ga, gb) = (ga(), gb());
+    let either = |i: usize| vec![hull(&[hull(&ga[i]), hull(&gb[i])])];
+    [either(0), either(1), either(2)]
+}
+
+fn negate_gradient(g: [Vec<Interval>; 3]) -> [Vec<Interval>; 3] {
+    let negate = |i: usize| g[i].iter().map(|d| d.neg()).collect();
+    [negate(0), negate(1), negate(2)]
+}
+
+fn bounds<F: Function>(f: &F, bindings: &HashMap<char, Interval>) -> Interval {
+    hull(&f.evaluate_interval(bindings))
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Union<A, B> {
+    pub a: A,
+    pub b: B,
+}
+
+impl<A: Function, B: Function> Union<A, B> {
+    pub fn new(a: A, b: B) -> Union<A, B> {
+        Union { a, b }
+    }
+}
+
+impl<A: Function, B: Function> Function for Union<A, B> {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        self.a.evaluate(x, y, z).min(self.b.evaluate(x, y, z))
+    }
+
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        combine_intervals(
+            &self.a.evaluate_interval(bindings),
+            &self.b.evaluate_interval(bindings),
+            |(a, b)| vec![a.minimum(b)],
+        )
+    }
+
+    fn gradient(&self, x: f32, y: f32, z: f32) -> Vector3<f32> {
+        if self.a.evaluate(x, y, z) <= self.b.evaluate(x, y, z) {
+            self.a.gradient(x, y, z)
+        } else {
+            self.b.gradient(x, y, z)
+        }
+    }
+
+    fn evaluate_gradient_interval(
+        &self,
+        bindings: &HashMap<char, Interval>,
+    ) -> [Vec<Interval>; 3] {
+        let (a, b) = (bounds(&self.a, bindings), bounds(&self.b, bindings));
+        select_gradient(
+            a.max < b.min,
+            b.max < a.min,
+            || self.a.evaluate_gradient_interval(bindings),
+            || self.b.evaluate_gradient_interval(bindings),
+        )
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Intersection<A, B> {
+    pub a: A,
+    pub b: B,
+}
+
+impl<A: Function, B: Function> Intersection<A, B> {
+    pub fn new(a: A, b: B) -> Intersection<A, B> {
+        Intersection { a, b }
+    }
+}
+
+impl<A: Function, B: Function> Function for Intersection<A, B> {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        self.a.evaluate(x, y, z).max(self.b.evaluate(x, y, z))
+    }
+
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        combine_intervals(
+            &self.a.evaluate_interval(bindings),
+            &self.b.evaluate_interval(bindings),
+            |(a, b)| vec![a.maximum(b)],
+        )
+    }
+
+    fn gradient(&self, x: f32, y: f32, z: f32) -> Vector3<f32> {
+        if self.a.evaluate(x, y, z) >= self.b.evaluate(x, y, z) {
+            self.a.gradient(x, y, z)
+        } else {
+            self.b.gradient(x, y, z)
+        }
+    }
+
+    fn evaluate_gradient_interval(
+        &self,
+        bindings: &HashMap<char, Interval>,
+    ) -> [Vec<Interval>; 3] {
+        let (a, b) = (bounds(&self.a, bindings), bounds(&self.b, bindings));
+        select_gradient(
+            a.min > b.max,
+            b.min > a.max,
+            || self.a.evaluate_gradient_interval(bindings),
+            || self.b.evaluate_gradient_interval(bindings),
+        )
+    }
+}
+
+// a with b cut out of it
+#[derive(Copy, Clone, Debug)]
+pub struct Difference<A, B> {
+    pub a: A,
+    pub b: B,
+}
+
+impl<A: Function, B: Function> Difference<A, B> {
+    pub fn new(a: A, b: B) -> Difference<A, B> {
+        Difference { a, b }
+    }
+}
+
+impl<A: Function, B: Function> Function for Difference<A, B> {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        self.a.evaluate(x, y, z).max(-self.b.evaluate(x, y, z))
+    }
+
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        combine_intervals(
+            &self.a.evaluate_interval(bindings),
+            &self.b.evaluate_interval(bindings),
+            |(a, b)| vec![a.maximum(&b.neg())],
+        )
+    }
+
+    fn gradient(&self, x: f32, y: f32, z: f32) -> Vector3<f32> {
+        if self.a.evaluate(x, y, z) >= -self.b.evaluate(x, y, z) {
+            self.a.gradient(x, y, z)
+        } else {
+            -self.b.gradient(x, y, z)
+        }
+    }
+
+    fn evaluate_gradient_interval(
+        &self,
+        bindings: &HashMap<char, Interval>,
+    ) -> [Vec<Interval>; 3] {
+        let (a, b) = (bounds(&self.a, bindings), bounds(&self.b, bindings).neg());
+        select_gradient(
+            a.min > b.max,
+            b.min > a.max,
+            || self.a.evaluate_gradient_interval(bindings),
+            || negate_gradient(self.b.evaluate_gradient_interval(bindings)),
+        )
+    }
+}
+
+// Polynomial smooth min, blending over a band of width k where the two
+// surfaces are close
+fn smooth_min(a: f32, b: f32, k: f32) -> f32 {
+    let h = (k - (a - b).abs()).max(0.0) / k;
+    a.min(b) - h * h * k * 0.25
+}
+
+// How much of the larger side's gradient leaks into the smooth min, between
+// 0 outside the band and 1/2 where the two are equal
+fn smooth_min_weight(a: f32, b: f32, k: f32) -> f32 {
+    (k - (a - b).abs()).max(0.0) / k * 0.5
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct SmoothUnion<A, B> {
+    pub a: A,
+    pub b: B,
+    pub k: f32,
+}
+
+impl<A: Function, B: Function> SmoothUnion<A, B> {
+    pub fn new(a: A, b: B, k: f32) -> SmoothUnion<A, B> {
+        SmoothUnion { a, b, k }
+    }
+}
+
+impl<A: Function, B: Function> Function for SmoothUnion<A, B> {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        smooth_min(self.a.evaluate(x, y, z), self.b.evaluate(x, y, z), self.k)
+    }
+
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        let k = self.k;
+        combine_intervals(
+            &self.a.evaluate_interval(bindings),
+            &self.b.evaluate_interval(bindings),
+            |(a, b)| {
+                if a.is_empty() || b.is_empty() {
+                    return vec![Interval::empty()];
+                }
+                vec![Interval::widened(
+                    smooth_min(a.min, b.min, k),
+                    smooth_min(a.max, b.max, k),
+                )]
+            },
+        )
+    }
+
+    fn gradient(&self, x: f32, y: f32, z: f32) -> Vector3<f32> {
+        let (a, b) = (self.a.evaluate(x, y, z), self.b.evaluate(x, y, z));
+        let w = smooth_min_weight(a, b, self.k);
+        let (ga, gb) = (self.a.gradient(x, y, z), self.b.gradient(x, y, z));
+        if a <= b {
+            ga * (1.0 - w) + gb * w
+        } else {
+            ga * w + gb * (1.0 - w)
+        }
+    }
+
+    // Blended gradients are convex combinations of the two sides
+    fn evaluate_gradient_interval(
+        &self,
+        bindings: &HashMap<char, Interval>,
+    ) -> [Vec<Interval>; 3] {
+        let (a, b) = (bounds(&self.a, bindings), bounds(&self.b, bindings));
+        select_gradient(
+            a.max + self.k <= b.min,
+            b.max + self.k <= a.min,
+            || self.a.evaluate_gradient_interval(bindings),
+            || self.b.evaluate_gradient_interval(bindings),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Vector3;
+    use interval::{contains_zero, hull};
+    use mesh_tree::MeshTree;
+    use parser::parse_expression;
+    use sdf::{Cuboid, Sphere};
+    use test_util::{assert_bounds, cell};
+
+    fn spheres() -> (Sphere, Sphere) {
+        (
+            Sphere::new(Vector3::new(-0.5, 0.0, 0.0), 0.75),
+            Sphere::new(Vector3::new(0.5, 0.0, 0.0), 0.75),
+        )
+    }
+
+    #[test]
+    fn union_intersection_difference() {
+        let (a, b) = spheres();
+
+        let u = Union::new(a, b);
+        assert!(u.evaluate(-1.0, 0.0, 0.0) < 0.0);
+        assert!(u.evaluate(1.0, 0.0, 0.0) < 0.0);
+        assert!(u.evaluate(0.0, 1.0, 0.0) > 0.0);
+
+        let i = Intersection::new(a, b);
+        assert!(i.evaluate(0.0, 0.0, 0.0) < 0.0);
+        assert!(i.evaluate(-1.0, 0.0, 0.0) > 0.0);
+
+        let d = Difference::new(a, b);
+        assert!(d.evaluate(-1.0, 0.0, 0.0) < 0.0);
+        assert!(d.evaluate(0.0, 0.0, 0.0) > 0.0);
+
+        let bb = cell((-2.0, 0.1), (-0.3, 0.4), (0.0, 1.0));
+        assert_bounds(&u, &bb);
+        assert_bounds(&i, &bb);
+        assert_bounds(&d, &bb);
+
+        // A cell inside both spheres is inside the union but on the far side
+        // of the difference
+        let inside = cell((-0.1, 0.1), (-0.1, 0.1), (-0.1, 0.1));
+        assert!(!contains_zero(&u.evaluate_interval(&inside.bindings())));
+        assert!(!contains_zero(&d.evaluate_interval(&inside.bindings())));
+    }
+
+    #[test]
+    fn smooth_union() {
+        let (a, b) = spheres();
+        let s = SmoothUnion::new(a, b, 0.5);
+        let u = Union::new(a, b);
+
+        // Blending only ever adds material, and only near both surfaces
+        assert!(s.evaluate(0.0, 0.7, 0.0) < u.evaluate(0.0, 0.7, 0.0));
+        assert_similiar!(s.evaluate(-2.0, 0.0, 0.0), u.evaluate(-2.0, 0.0, 0.0));
+
+        assert_bounds(&s, &cell((-2.0, 0.1), (-0.3, 0.4), (0.0, 1.0)));
+        assert_bounds(&s, &cell((-0.2, 0.2), (0.5, 0.9), (-0.2, 0.2)));
+    }
+
+    #[test]
+    fn gradients() {
+        let (a, b) = spheres();
+        let u = Union::new(a, b);
+        let g = u.gradient(-1.5, 0.0, 0.0);
+        assert_similiar!(g.x, -1.0);
+        assert_similiar!(u.gradient(1.5, 0.0, 0.0).x, 1.0);
+
+        let d = Difference::new(a, b);
+        // Inside b the value grows towards b's center, deeper into the cut
+        assert_similiar!(d.gradient(0.1, 0.0, 0.0).x, 1.0);
+
+        // The smooth union's gradient is finite differences of its value
+        let s = SmoothUnion::new(a, b, 0.5);
+        let h = 1e-3;
+        let fd = (s.evaluate(0.1 + h, 0.7, 0.0) - s.evaluate(0.1 - h, 0.7, 0.0)) / (2.0 * h);
+        assert!((s.gradient(0.1, 0.7, 0.0).x - fd).abs() < 1e-2);
+
+        // Interval gradients cover the point gradients, including across
+        // the switch between sides
+        let bb = cell((-0.2, 0.2), (0.5, 0.9), (-0.2, 0.2));
+        for f in &[&u as &dyn Function, &d, &s, &Intersection::new(a, b)] {
+            let bounds = f.evaluate_gradient_interval(&bb.bindings());
+            for &(x, y) in &[(-0.2, 0.5), (0.0, 0.7), (0.2, 0.9)] {
+                let g = f.gradient(x, y, 0.0);
+                let x_bound = hull(&bounds[0]);
+                assert!(g.x >= x_bound.min - 1e-2 && g.x <= x_bound.max + 1e-2);
+            }
+        }
+    }
+
+    #[test]
+    fn mesh_scene() {
+        let input: Vec<char> = "z + 0.5".chars().collect();
+        let floor = parse_expression(&input, 0).unwrap();
+        let cube = Cuboid::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.5, 0.5, 0.5));
+        let (a, _) = spheres();
+        let scene = Union::new(Difference::new(cube, a), *floor);
+
+        let bb = cell((-1.0, 1.0), (-1.0, 1.0), (-1.0, 1.0));
+        let mut tree = MeshTree::new(Box::new(scene), bb);
+        for _ in 0..3 {
+            tree.next_level().unwrap();
+        }
+        tree.generate_vertex_map();
+        tree.generate_triangle_set();
+        assert_eq!(tree.level, 3);
+    }
+}
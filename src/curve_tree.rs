@@ -0,0 +1,362 @@
+use cgmath::{InnerSpace, Vector2};
+use function::*;
+use geoprim::*;
+use interval::{contains_zero, is_bounded, merge_intervals, Interval};
+use itertools::Itertools;
+use key::NeighborRelation;
+use key2d;
+use key2d::Key2D;
+use mesh_tree_error::{MeshTreeError, MeshTreeResult};
+use std::collections::{HashMap, HashSet};
+
+// Curves f(x, y) = 0 in the z = 0 plane, found with a quadtree the same way
+// MeshTree finds surfaces with an octree.
+
+#[derive(Debug, Copy, Clone)]
+pub struct BoundingRect {
+    pub x: Interval,
+    pub y: Interval,
+}
+
+impl BoundingRect {
+    // Children in key order, x major
+    pub fn split(&self) -> Vec<BoundingRect> {
+        self.x
+            .split()
+            .iter()
+            .cartesian_product(self.y.split().iter())
+            .map(|(x, y)| BoundingRect { x: *x, y: *y })
+            .collect()
+    }
+
+    pub fn bindings(&self) -> HashMap<char, Interval> {
+        let mut bindings = HashMap::new();
+        bindings.insert('x', self.x);
+        bindings.insert('y', self.y);
+        bindings.insert('z', Interval::new(0.0, 0.0));
+        bindings
+    }
+
+    pub fn center(&self) -> Vector2<f32> {
+        Vector2::new(self.x.middle(), self.y.middle())
+    }
+
+    pub fn clamp_vector(&self, v: &mut Vector2<f32>) {
+        v.x = self.x.clamp_value(v.x);
+        v.y = self.y.clamp_value(v.y);
+    }
+
+    // Natural and centered forms, as in BoundingBox::contains_root, with the
+    // centered form entire where f(c) or the gradient is unbounded
+    pub fn contains_root<F: Function + ?Sized>(&self, f: &Box<F>) -> bool {
+        contains_zero(&f.evaluate_interval(&self.bindings()))
+            && contains_zero(&self.centered_form(f))
+    }
+
+    pub fn centered_form<F: Function + ?Sized>(&self, f: &Box<F>) -> Vec<Interval> {
+        let c = self.center();
+        let point = BoundingRect {
+            x: Interval::new(c.x, c.x),
+            y: Interval::new(c.y, c.y),
+        };
+        let gradient = f.evaluate_gradient_interval(&self.bindings());
+
+        let mut result = f.evaluate_interval(&point.bindings());
+        if !is_bounded(&result) || !is_bounded(&gradient[0]) || !is_bounded(&gradient[1]) {
+            return vec![Interval::entire()];
+        }
+
+        for (axis, offset) in [self.x.sub(&point.x), self.y.sub(&point.y)].iter().enumerate() {
+            let terms: Vec<Interval> = gradient[axis]
+                .iter()
+                .cartesian_product(offset)
+                .flat_map(|(derivative, offset)| derivative.mul(offset))
+                .collect();
+
+            result = merge_intervals(
+                result
+                    .iter()
+                    .cartesian_product(&terms)
+                    .flat_map(|(value, term)| value.add(term))
+                    .collect(),
+            );
+        }
+
+        result
+    }
+
+    // The center moved one gradient step towards the curve, kept in the rect
+    pub fn curve_vertex<F: Function + ?Sized>(&self, f: &Box<F>) -> Vector2<f32> {
+        let c = self.center();
+        let value = f.evaluate(c.x, c.y, 0.0);
+        let g = f.gradient(c.x, c.y, 0.0);
+        let g = Vector2::new(g.x, g.y);
+        let length2 = g.magnitude2();
+        if !(length2 > 0.0) || !length2.is_finite() || !value.is_finite() {
+            return c;
+        }
+
+        let mut projected = c - g * (value / length2);
+        self.clamp_vector(&mut projected);
+        projected
+    }
+
+    pub fn add_to_plot(&self, plot: &mut Plot) {
+        let corners = [
+            Point::new(self.x.min, self.y.min, 0.0),
+            Point::new(self.x.max, self.y.min, 0.0),
+            Point::new(self.x.max, self.y.max, 0.0),
+            Point::new(self.x.min, self.y.max, 0.0),
+        ];
+        for i in 0..4 {
+            plot.add_line(LineSegment::new(corners[i], corners[(i + 1) % 4]));
+        }
+    }
+}
+
+pub struct CurveTree<K: Key2D, F: Function + ?Sized> {
+    function: Box<F>,
+    pub level: u32,
+    solution_map: HashMap<K, BoundingRect>,
+    vertex_map: HashMap<K, Vector2<f32>>,
+    edge_set: HashSet<(K, K)>,
+}
+
+impl<F: Function + ?Sized> CurveTree<key2d::MortonKey2D, F> {
+    pub fn new(f: Box<F>, rect: BoundingRect) -> CurveTree<key2d::MortonKey2D, F> {
+        CurveTree::with_key(f, rect)
+    }
+}
+
+impl<K: Key2D, F: Function + ?Sized> CurveTree<K, F> {
+    pub fn with_key(f: Box<F>, rect: BoundingRect) -> CurveTree<K, F> {
+        let mut result = CurveTree {
+            function: f,
+            level: 0,
+            solution_map: HashMap::new(),
+            vertex_map: HashMap::new(),
+            edge_set: HashSet::new(),
+        };
+
+        if rect.contains_root(&result.function) {
+            result.solution_map.insert(K::root_key(), rect);
+        }
+
+        result
+    }
+
+    pub fn next_level(&mut self) -> MeshTreeResult<()> {
+        if self.level >= K::max_level() {
+            return Err(MeshTreeError::MaxLevel {
+                max_level: K::max_level(),
+            });
+        }
+
+        self.vertex_map.clear();
+        self.edge_set.clear();
+        self.level += 1;
+
+        let mut new_solution_map = HashMap::new();
+        for (key, rect) in &self.solution_map {
+            for (i, child) in rect.split().into_iter().enumerate() {
+                if child.contains_root(&self.function) {
+                    new_solution_map.insert(key.child_key(i as u64), child);
+                }
+            }
+        }
+
+        self.solution_map = new_solution_map;
+        Ok(())
+    }
+
+    pub fn generate_vertex_map(&mut self) {
+        self.vertex_map.clear();
+        for (key, rect) in &self.solution_map {
+            self.vertex_map
+                .insert(key.clone(), rect.curve_vertex(&self.function));
+        }
+    }
+
+    // Joins the vertices of neighboring cells when the function has opposite
+    // signs at the two ends of the side they share, which is dual contouring
+    // in two dimensions. Only the ends are sampled, so a curve crossing a
+    // side twice leaves no sign change there and gets no edge.
+    pub fn generate_edge_set(&mut self) {
+        self.edge_set.clear();
+        let f = &self.function;
+        for (key, rect) in &self.solution_map {
+            let more = [
+                (NeighborRelation::More, NeighborRelation::Same),
+                (NeighborRelation::Same, NeighborRelation::More),
+            ];
+            for &(x, y) in more.iter() {
+                let neighbor = match key.neighbor_key(key2d::Neighbor2D::from_components(x, y)) {
+                    Some(neighbor) if self.solution_map.contains_key(&neighbor) => neighbor,
+                    _ => continue,
+                };
+
+                let (a, b) = if x == NeighborRelation::More {
+                    (
+                        f.evaluate(rect.x.max, rect.y.min, 0.0),
+                        f.evaluate(rect.x.max, rect.y.max, 0.0),
+                    )
+                } else {
+                    (
+                        f.evaluate(rect.x.min, rect.y.max, 0.0),
+                        f.evaluate(rect.x.max, rect.y.max, 0.0),
+                    )
+                };
+                if (a < 0.0) != (b < 0.0) {
+                    self.edge_set.insert((key.clone(), neighbor));
+                }
+            }
+        }
+    }
+
+    // The edges chained into polylines, open ones first from their ends,
+    // then closed loops which repeat their first vertex at the end
+    pub fn polylines(&self) -> Vec<Vec<Vector2<f32>>> {
+        let mut adjacent: HashMap<K, Vec<K>> = HashMap::new();
+        let mut edges: Vec<&(K, K)> = self.edge_set.iter().collect();
+        edges.sort();
+        for &&(a, b) in &edges {
+            adjacent.entry(a).or_insert_with(Vec::new).push(b);
+            adjacent.entry(b).or_insert_with(Vec::new).push(a);
+        }
+
+        let mut starts: Vec<K> = adjacent.keys().cloned().collect();
+        starts.sort_by_key(|k| (adjacent[k].len() != 1, *k));
+
+        let mut used: HashSet<(K, K)> = HashSet::new();
+        let mut lines = Vec::new();
+        for start in starts {
+            let mut current = start;
+            let mut line = vec![current];
+            while let Some(&next) = adjacent[&current]
+                .iter()
+                .find(|&&n| !used.contains(&(current.min(n), current.max(n))))
+            {
+                used.insert((current.min(next), current.max(next)));
+                line.push(next);
+                current = next;
+            }
+
+            if line.len() > 1 {
+                lines.push(line.iter().map(|k| self.vertex_map[k]).collect());
+            }
+        }
+
+        lines
+    }
+
+    pub fn add_to_plot(&self, add_rects: bool, add_curve: bool, plot: &mut Plot) {
+        if add_rects {
+            for rect in self.solution_map.values() {
+                rect.add_to_plot(plot);
+            }
+        }
+
+        if add_curve {
+            for line in self.polylines() {
+                for (a, b) in line.iter().tuple_windows() {
+                    plot.add_line(LineSegment::new(
+                        Point::new(a.x, a.y, 0.0),
+                        Point::new(b.x, b.y, 0.0),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parser::parse_expression;
+
+    fn rect(x: (f32, f32), y: (f32, f32)) -> BoundingRect {
+        BoundingRect {
+            x: Interval::new(x.0, x.1),
+            y: Interval::new(y.0, y.1),
+        }
+    }
+
+    fn curve(expression: &str) -> Box<::function_ir::Node> {
+        let input: Vec<char> = expression.chars().collect();
+        parse_expression(&input, 0).unwrap()
+    }
+
+    fn plot(expression: &str, levels: u32) -> CurveTree<key2d::MortonKey2D, ::function_ir::Node> {
+        let mut tree = CurveTree::new(curve(expression), rect((-1.5, 1.5), (-1.5, 1.5)));
+        for _ in 0..levels {
+            tree.next_level().unwrap();
+        }
+        tree.generate_vertex_map();
+        tree.generate_edge_set();
+        tree
+    }
+
+    #[test]
+    fn split_matches_keys() {
+        let r = rect((0.0, 2.0), (0.0, 2.0));
+        let children = r.split();
+        // Child 1 is up, child 2 is right, as in MortonKey2D
+        assert_eq!(children[1].y.min, 1.0);
+        assert_eq!(children[1].x.min, 0.0);
+        assert_eq!(children[2].x.min, 1.0);
+        assert_eq!(children[2].y.min, 0.0);
+    }
+
+    #[test]
+    fn undefined_center_keeps_root() {
+        let f = curve("1/x - y");
+        assert!(rect((-1.0, 1.0), (-1.0, 3.0)).contains_root(&f));
+    }
+
+    #[test]
+    fn circle_is_one_loop() {
+        let tree = plot("x^2 + y^2 - 1", 5);
+        let lines = tree.polylines();
+        assert_eq!(lines.len(), 1);
+
+        let line = &lines[0];
+        assert_eq!(line.first(), line.last());
+        assert_eq!(line.len() - 1, tree.edge_set.len());
+        for v in line {
+            assert!((v.magnitude() - 1.0).abs() < 0.02);
+        }
+    }
+
+    #[test]
+    fn parabola_is_open() {
+        let tree = plot("y - x^2 + 0.5", 5);
+        let lines = tree.polylines();
+        assert_eq!(lines.len(), 1);
+
+        // It leaves through the top of the square at both ends
+        let line = &lines[0];
+        assert!(line.first() != line.last());
+        assert!(line[0].y > 1.3 && line[line.len() - 1].y > 1.3);
+        assert!(line[0].x * line[line.len() - 1].x < 0.0);
+    }
+
+    #[test]
+    fn two_circles() {
+        let tree = plot("((x - 0.7)^2 + y^2 - 0.16) * ((x + 0.7)^2 + y^2 - 0.16)", 5);
+        let lines = tree.polylines();
+        assert_eq!(lines.len(), 2);
+        for line in &lines {
+            assert_eq!(line.first(), line.last());
+        }
+    }
+
+    #[test]
+    fn stops_at_key_capacity() {
+        let f = ConstFunction { c: 1.0 };
+        let mut tree = CurveTree::new(Box::new(f), rect((0.0, 1.0), (0.0, 1.0)));
+        for _ in 0..key2d::COMPONENT_BIT_COUNT_2D {
+            tree.next_level().unwrap();
+        }
+        assert!(tree.next_level().is_err());
+    }
+}
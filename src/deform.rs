@@ -0,0 +1,287 @@
+use cgmath::Vector3;
+use function::Function;
+use interval::{hull, Interval};
+use std::collections::HashMap;
+
+// Non-rigid deformations of the input space. None of these preserve distance,
+// so a distance field going in comes out as just a field with the same zero
+// set. The interval bounds are loose, but they always cover the deformed
+// box, which is what keeps culling in BoundingBox::contains_root sound.
+
+fn scale(i: Interval, c: f32) -> Interval {
+    hull(&i.mul(&Interval::new(c, c)))
+}
+
+// (x, y) rotated by an angle somewhere in theta. Each product is bounded on
+// its own, then clipped to the radius, which rotation can't change.
+fn rotate_interval(x: Interval, y: Interval, theta: Interval) -> (Interval, Interval) {
+    let (c, s) = (theta.cos(), theta.sin());
+    let rx = hull(&hull(&c.mul(&x)).sub(&hull(&s.mul(&y))));
+    let ry = hull(&hull(&s.mul(&x)).add(&hull(&c.mul(&y))));
+
+    let r = hull(&x.square().add(&y.square())).sqrt();
+    let radius = Interval::new(-r.max, r.max);
+    (rx.intersect(&radius), ry.intersect(&radius))
+}
+
+fn with_coordinates(
+    bindings: &HashMap<char, Interval>,
+    x: Interval,
+    y: Interval,
+    z: Interval,
+) -> HashMap<char, Interval> {
+    let mut deformed = bindings.clone();
+    deformed.insert('x', x);
+    deformed.insert('y', y);
+    deformed.insert('z', z);
+    deformed
+}
+
+// Rotates each slice around the z axis by rate * z radians
+#[derive(Copy, Clone, Debug)]
+pub struct Twist<F> {
+    pub function: F,
+    pub rate: f32,
+}
+
+impl<F: Function> Twist<F> {
+    pub fn new(function: F, rate: f32) -> Twist<F> {
+        Twist { function, rate }
+    }
+}
+
+impl<F: Function> Function for Twist<F> {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        let (s, c) = (self.rate * z).sin_cos();
+        self.function.evaluate(c * x - s * y, s * x + c * y, z)
+    }
+
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        let (x, y, z) = (bindings[&'x'], bindings[&'y'], bindings[&'z']);
+        let (rx, ry) = rotate_interval(x, y, scale(z, self.rate));
+        self.function
+            .evaluate_interval(&with_coordinates(bindings, rx, ry, z))
+    }
+}
+
+// Bends the x axis around z, rotating by rate * x radians
+#[derive(Copy, Clone, Debug)]
+pub struct Bend<F> {
+    pub function: F,
+    pub rate: f32,
+}
+
+impl<F: Function> Bend<F> {
+    pub fn new(function: F, rate: f32) -> Bend<F> {
+        Bend { function, rate }
+    }
+}
+
+impl<F: Function> Function for Bend<F> {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        let (s, c) = (self.rate * x).sin_cos();
+        self.function.evaluate(c * x - s * y, s * x + c * y, z)
+    }
+
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        let (x, y, z) = (bindings[&'x'], bindings[&'y'], bindings[&'z']);
+        let (rx, ry) = rotate_interval(x, y, scale(x, self.rate));
+        self.function
+            .evaluate_interval(&with_coordinates(bindings, rx, ry, z))
+    }
+}
+
+// Scales each slice by 1 + rate * z, growing the shape along z for positive
+// rates
+#[derive(Copy, Clone, Debug)]
+pub struct Taper<F> {
+    pub function: F,
+    pub rate: f32,
+}
+
+impl<F: Function> Taper<F> {
+    pub fn new(function: F, rate: f32) -> Taper<F> {
+        Taper { function, rate }
+    }
+}
+
+impl<F: Function> Function for Taper<F> {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        let s = 1.0 + self.rate * z;
+        self.function.evaluate(x / s, y / s, z)
+    }
+
+    // Where the scale passes through zero the division is unbounded, which is
+    // loose but still covers everything
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        let (x, y, z) = (bindings[&'x'], bindings[&'y'], bindings[&'z']);
+        let s = hull(&scale(z, self.rate).add(&Interval::new(1.0, 1.0)));
+        let (tx, ty) = (hull(&x.div(&s)), hull(&y.div(&s)));
+        self.function
+            .evaluate_interval(&with_coordinates(bindings, tx, ty, z))
+    }
+}
+
+// Tiles space with copies of the function every period along each axis. A
+// period of zero leaves that axis alone. With a limit, only copies -limit to
+// limit are made and the outermost ones extend to infinity.
+#[derive(Copy, Clone, Debug)]
+pub struct Repeat<F> {
+    pub function: F,
+    pub period: Vector3<f32>,
+    pub limit: Option<Vector3<f32>>,
+}
+
+impl<F: Function> Repeat<F> {
+    pub fn new(function: F, period: Vector3<f32>) -> Repeat<F> {
+        Repeat {
+            function,
+            period,
+            limit: None,
+        }
+    }
+
+    pub fn limited(function: F, period: Vector3<f32>, limit: Vector3<f32>) -> Repeat<F> {
+        Repeat {
+            function,
+            period,
+            limit: Some(limit),
+        }
+    }
+
+    fn cell(&self, p: f32, period: f32, limit: Option<f32>) -> f32 {
+        let k = (p / period).round();
+        match limit {
+            Some(l) => k.max(-l).min(l),
+            None => k,
+        }
+    }
+
+    fn repeat(&self, p: f32, period: f32, limit: Option<f32>) -> f32 {
+        if period <= 0.0 {
+            return p;
+        }
+
+        p - period * self.cell(p, period, limit)
+    }
+
+    // The cell index is monotone in p. Within one cell it's just a shift,
+    // across several we get the partial ends plus full cells in between,
+    // which cover -period/2 to period/2.
+    fn repeat_interval(&self, i: Interval, period: f32, limit: Option<f32>) -> Interval {
+        if period <= 0.0 || i.is_empty() {
+            return i;
+        }
+
+        let k_min = self.cell(i.min, period, limit);
+        let k_max = self.cell(i.max, period, limit);
+        let shift = |i: Interval, k: f32| hull(&i.sub(&Interval::new(k * period, k * period)));
+
+        if k_min == k_max {
+            return shift(i, k_min);
+        }
+
+        let first = shift(Interval::new(i.min, i.min), k_min);
+        let last = shift(Interval::new(i.max, i.max), k_max);
+        Interval::new(
+            first.min.min(-period / 2.0),
+            last.max.max(period / 2.0),
+        )
+    }
+}
+
+impl<F: Function> Function for Repeat<F> {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        let limit = self.limit;
+        self.function.evaluate(
+            self.repeat(x, self.period.x, limit.map(|l| l.x)),
+            self.repeat(y, self.period.y, limit.map(|l| l.y)),
+            self.repeat(z, self.period.z, limit.map(|l| l.z)),
+        )
+    }
+
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        let limit = self.limit;
+        let (x, y, z) = (bindings[&'x'], bindings[&'y'], bindings[&'z']);
+        self.function.evaluate_interval(&with_coordinates(
+            bindings,
+            self.repeat_interval(x, self.period.x, limit.map(|l| l.x)),
+            self.repeat_interval(y, self.period.y, limit.map(|l| l.y)),
+            self.repeat_interval(z, self.period.z, limit.map(|l| l.z)),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mesh_tree::BoundingBox;
+    use sdf::{Cuboid, Sphere};
+    use test_util::{assert_bounds, cell};
+
+    fn bar() -> Cuboid {
+        Cuboid::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.2, 2.0))
+    }
+
+    fn boxes() -> Vec<BoundingBox> {
+        vec![
+            cell((-2.0, 2.0), (-2.0, 2.0), (-2.0, 2.0)),
+            cell((0.5, 1.5), (-0.25, 0.25), (0.0, 0.5)),
+            cell((-0.1, 0.1), (0.3, 0.4), (0.7, 0.9)),
+            cell((-7.0, -5.5), (2.5, 3.75), (-1.0, 3.0)),
+        ]
+    }
+
+    #[test]
+    fn twist() {
+        let t = Twist::new(bar(), ::std::f32::consts::FRAC_PI_2);
+        // No twist at z = 0, a quarter turn at z = 1
+        assert_similiar!(t.evaluate(0.9, 0.0, 0.0), bar().evaluate(0.9, 0.0, 0.0));
+        assert!(t.evaluate(0.0, 0.9, 1.0) < 0.0);
+        assert!(t.evaluate(0.9, 0.0, 1.0) > 0.0);
+        for bb in boxes() {
+            assert_bounds(&t, &bb);
+        }
+    }
+
+    #[test]
+    fn bend() {
+        let b = Bend::new(bar(), 0.5);
+        assert_similiar!(b.evaluate(0.0, 0.0, 0.0), bar().evaluate(0.0, 0.0, 0.0));
+        for bb in boxes() {
+            assert_bounds(&b, &bb);
+        }
+    }
+
+    #[test]
+    fn taper() {
+        let t = Taper::new(bar(), 0.5);
+        assert_similiar!(t.evaluate(0.5, 0.0, 0.0), bar().evaluate(0.5, 0.0, 0.0));
+        // At z = 0.5 the bar is 1.25 times as wide
+        assert!(t.evaluate(1.2, 0.0, 0.5) < 0.0);
+        assert!(t.evaluate(1.2, 0.0, 0.0) > 0.0);
+        for bb in boxes() {
+            assert_bounds(&t, &bb);
+        }
+    }
+
+    #[test]
+    fn repeat() {
+        let s = Sphere::new(Vector3::new(0.0, 0.0, 0.0), 0.25);
+        let r = Repeat::new(s, Vector3::new(1.0, 1.0, 0.0));
+        assert_similiar!(r.evaluate(3.0, -2.0, 0.0), -0.25);
+        assert_similiar!(r.evaluate(3.5, 0.0, 0.0), 0.25);
+        assert_similiar!(r.evaluate(0.0, 0.0, 2.0), 1.75);
+        for bb in boxes() {
+            assert_bounds(&r, &bb);
+        }
+
+        let l = Repeat::limited(s, Vector3::new(1.0, 0.0, 0.0), Vector3::new(2.0, 0.0, 0.0));
+        assert_similiar!(l.evaluate(2.0, 0.0, 0.0), -0.25);
+        assert_similiar!(l.evaluate(3.0, 0.0, 0.0), 0.75);
+        assert_similiar!(l.evaluate(-5.0, 0.0, 0.0), 2.75);
+        for bb in boxes() {
+            assert_bounds(&l, &bb);
+        }
+    }
+}
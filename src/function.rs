@@ -1,10 +1,118 @@
+use cgmath::Vector3;
 use interval::Interval;
 use std::collections::HashMap;
+use std::sync::Arc;
 
-pub trait Function: Sized {
+// Step for the finite difference gradient. Small enough to resolve detail at
+// the depths we mesh to, large enough that f32 cancellation stays small.
+const GRADIENT_STEP: f32 = 1e-3;
+
+pub trait Function {
     fn evaluate(&self, x: f32, y: f32, z: f32) -> f32;
 
     fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval>;
+
+    // The gradient at a point, by central differences unless the function
+    // knows better
+    fn gradient(&self, x: f32, y: f32, z: f32) -> Vector3<f32> {
+        let h = GRADIENT_STEP;
+        Vector3::new(
+            self.evaluate(x + h, y, z) - self.evaluate(x - h, y, z),
+            self.evaluate(x, y + h, z) - self.evaluate(x, y - h, z),
+            self.evaluate(x, y, z + h) - self.evaluate(x, y, z - h),
+        ) / (2.0 * h)
+    }
+
+    // Bounds on the x, y and z partial derivatives over the box in the
+    // bindings. Without knowing more about the function anything is possible.
+    fn evaluate_gradient_interval(
+        &self,
+        _bindings: &HashMap<char, Interval>,
+    ) -> [Vec<Interval>; 3] {
+        [
+            vec![Interval::entire()],
+            vec![Interval::entire()],
+            vec![Interval::entire()],
+        ]
+    }
+}
+
+// Forward through references and Arc, so shared functions like
+// `Arc<dyn Function + Send + Sync>` can be meshed and stored side by side.
+// `Box<dyn Function>` needs no impl, MeshTree takes its function boxed. (A Box
+// impl would shadow Node's own `evaluate` on `Box<Node>`.)
+impl<'a, F: Function + ?Sized> Function for &'a F {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        (**self).evaluate(x, y, z)
+    }
+
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        (**self).evaluate_interval(bindings)
+    }
+
+    fn gradient(&self, x: f32, y: f32, z: f32) -> Vector3<f32> {
+        (**self).gradient(x, y, z)
+    }
+
+    fn evaluate_gradient_interval(
+        &self,
+        bindings: &HashMap<char, Interval>,
+    ) -> [Vec<Interval>; 3] {
+        (**self).evaluate_gradient_interval(bindings)
+    }
+}
+
+impl<F: Function + ?Sized> Function for Arc<F> {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        (**self).evaluate(x, y, z)
+    }
+
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        (**self).evaluate_interval(bindings)
+    }
+
+    fn gradient(&self, x: f32, y: f32, z: f32) -> Vector3<f32> {
+        (**self).gradient(x, y, z)
+    }
+
+    fn evaluate_gradient_interval(
+        &self,
+        bindings: &HashMap<char, Interval>,
+    ) -> [Vec<Interval>; 3] {
+        (**self).evaluate_gradient_interval(bindings)
+    }
+}
+
+// A Function from a pair of closures, one for points and one for boxes. The
+// interval closure has to bound the point closure over the box in `bindings`
+// for meshing to be correct.
+pub struct ClosureFunction<P, I> {
+    point: P,
+    interval: I,
+}
+
+impl<P, I> ClosureFunction<P, I>
+where
+    P: Fn(f32, f32, f32) -> f32,
+    I: Fn(&HashMap<char, Interval>) -> Vec<Interval>,
+{
+    pub fn new(point: P, interval: I) -> ClosureFunction<P, I> {
+        ClosureFunction { point, interval }
+    }
+}
+
+impl<P, I> Function for ClosureFunction<P, I>
+where
+    P: Fn(f32, f32, f32) -> f32,
+    I: Fn(&HashMap<char, Interval>) -> Vec<Interval>,
+{
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        (self.point)(x, y, z)
+    }
+
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        (self.interval)(bindings)
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -23,4 +131,102 @@ impl Function for ConstFunction {
             max: self.c,
         }]
     }
+
+    fn gradient(&self, _x: f32, _y: f32, _z: f32) -> Vector3<f32> {
+        Vector3::new(0.0, 0.0, 0.0)
+    }
+
+    fn evaluate_gradient_interval(
+        &self,
+        _bindings: &HashMap<char, Interval>,
+    ) -> [Vec<Interval>; 3] {
+        let zero = Interval::new(0.0, 0.0);
+        [vec![zero], vec![zero], vec![zero]]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use function_ir::Node;
+    use interval::contains_zero;
+    use mesh_tree::{BoundingBox, MeshTree};
+    use parser::parse_expression;
+
+    fn plane() -> ClosureFunction<
+        impl Fn(f32, f32, f32) -> f32,
+        impl Fn(&HashMap<char, Interval>) -> Vec<Interval>,
+    > {
+        ClosureFunction::new(
+            |x, _, _| x - 0.5,
+            |bindings| bindings[&'x'].sub(&Interval::new(0.5, 0.5)),
+        )
+    }
+
+    fn unit_box() -> BoundingBox {
+        BoundingBox {
+            x: Interval::new(-1.0, 1.0),
+            y: Interval::new(-1.0, 1.0),
+            z: Interval::new(-1.0, 1.0),
+        }
+    }
+
+    #[test]
+    fn closure_function() {
+        let f = plane();
+        assert_similiar!(f.evaluate(1.0, 0.0, 0.0), 0.5);
+
+        let mut bindings = unit_box().bindings();
+        assert!(contains_zero(&f.evaluate_interval(&bindings)));
+        bindings.insert('x', Interval::new(-1.0, 0.0));
+        assert!(!contains_zero(&f.evaluate_interval(&bindings)));
+    }
+
+    #[test]
+    fn finite_difference_gradient() {
+        let f = ClosureFunction::new(
+            |x, y, z| x * x + 3.0 * y - z,
+            |_: &HashMap<char, Interval>| vec![Interval::entire()],
+        );
+        let g = f.gradient(0.5, 1.0, -2.0);
+        assert!((g.x - 1.0).abs() < 1e-2);
+        assert!((g.y - 3.0).abs() < 1e-2);
+        assert!((g.z + 1.0).abs() < 1e-2);
+
+        let boxed: Box<dyn Function> = Box::new(f);
+        assert!((boxed.gradient(0.5, 1.0, -2.0).x - 1.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn heterogeneous_functions() {
+        let input: Vec<char> = "x^2 + y^2 + z^2 - 1".chars().collect();
+        let sphere: Box<Node> = parse_expression(&input, 0).unwrap();
+        let functions: Vec<Box<dyn Function>> = vec![
+            sphere,
+            Box::new(plane()),
+            Box::new(ConstFunction { c: 1.0 }),
+        ];
+
+        assert_similiar!(functions[0].evaluate(1.0, 1.0, 0.0), 1.0);
+        assert_similiar!(functions[1].evaluate(1.0, 1.0, 0.0), 0.5);
+        assert_similiar!(functions[2].evaluate(1.0, 1.0, 0.0), 1.0);
+
+        let shared: Arc<dyn Function + Send + Sync> = Arc::new(ConstFunction { c: 2.0 });
+        assert_similiar!(shared.evaluate(0.0, 0.0, 0.0), 2.0);
+    }
+
+    #[test]
+    fn mesh_trait_objects() {
+        let f: Box<dyn Function> = Box::new(plane());
+        let mut tree = MeshTree::new(f, unit_box());
+        tree.next_level().unwrap();
+        tree.next_level().unwrap();
+        tree.generate_vertex_map();
+        assert!(tree.level == 2);
+
+        let shared: Arc<dyn Function + Send + Sync> = Arc::new(plane());
+        let mut tree = MeshTree::new(Box::new(shared), unit_box());
+        tree.next_level().unwrap();
+        assert!(tree.level == 1);
+    }
 }
@@ -1,18 +1,142 @@
+use cgmath::Vector3;
 use function::Function;
 use interval::*;
+use noise::{noise_derivative, noise_derivative_interval, Perlin};
 use std::collections::HashMap;
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub enum Node {
     Add(Box<Node>, Box<Node>),
     Sub(Box<Node>, Box<Node>),
     Mul(Box<Node>, Box<Node>),
     Div(Box<Node>, Box<Node>),
     Exp(Box<Node>, Box<Node>),
+    Ln(Box<Node>),
+    Sin(Box<Node>),
+    Cos(Box<Node>),
+    Abs(Box<Node>),
+    // Perlin noise at the three arguments, differentiated the given number
+    // of times along each of them
+    Noise([u8; 3], Box<Perlin>, Box<Node>, Box<Node>, Box<Node>),
     Variable(char),
     Constant(f32),
 }
 
+// Constructors for derivative trees that fold away the zeros and ones
+// differentiation produces, so the trees don't balloon.
+fn sum(n1: Node, n2: Node) -> Node {
+    match (n1, n2) {
+        (Node::Constant(c), n) | (n, Node::Constant(c)) if c == 0.0 => n,
+        (n1, n2) => Node::Add(Box::new(n1), Box::new(n2)),
+    }
+}
+
+fn difference(n1: Node, n2: Node) -> Node {
+    match (n1, n2) {
+        (n, Node::Constant(c)) if c == 0.0 => n,
+        (n1, n2) => Node::Sub(Box::new(n1), Box::new(n2)),
+    }
+}
+
+fn product(n1: Node, n2: Node) -> Node {
+    match (n1, n2) {
+        (Node::Constant(c), _) | (_, Node::Constant(c)) if c == 0.0 => Node::Constant(0.0),
+        (Node::Constant(c), n) | (n, Node::Constant(c)) if c == 1.0 => n,
+        (n1, n2) => Node::Mul(Box::new(n1), Box::new(n2)),
+    }
+}
+
+fn quotient(n1: Node, n2: Node) -> Node {
+    match (n1, n2) {
+        (Node::Constant(c), _) if c == 0.0 => Node::Constant(0.0),
+        (n, Node::Constant(c)) if c == 1.0 => n,
+        (n1, n2) => Node::Div(Box::new(n1), Box::new(n2)),
+    }
+}
+
+// Partials along x, y and z in forward mode. None is a partial that is zero
+// because the node doesn't depend on the variable, which keeps it out of
+// products the same way the constructors above fold zeros, so 0 * inf and
+// 0 / 0 don't turn up in gradients.
+type Partials<T> = [Option<T>; 3];
+
+const AXES: [char; 3] = ['x', 'y', 'z'];
+
+fn zip_partials<T, F>(a: Partials<T>, b: Partials<T>, mut op: F) -> Partials<T>
+where
+    F: FnMut(Option<T>, Option<T>) -> Option<T>,
+{
+    let [a0, a1, a2] = a;
+    let [b0, b1, b2] = b;
+    [op(a0, b0), op(a1, b1), op(a2, b2)]
+}
+
+fn map_partials<T, F>(a: Partials<T>, mut op: F) -> Partials<T>
+where
+    F: FnMut(T) -> T,
+{
+    let [a0, a1, a2] = a;
+    [a0.map(&mut op), a1.map(&mut op), a2.map(&mut op)]
+}
+
+fn sum_partial(a: Option<f32>, b: Option<f32>) -> Option<f32> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a + b),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+fn difference_partial(a: Option<f32>, b: Option<f32>) -> Option<f32> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a - b),
+        (a, None) => a,
+        (None, Some(b)) => Some(-b),
+    }
+}
+
+fn add_intervals(a: &[Interval], b: &[Interval]) -> Vec<Interval> {
+    combine_intervals(a, b, |(i1, i2)| i1.add(i2))
+}
+
+fn sub_intervals(a: &[Interval], b: &[Interval]) -> Vec<Interval> {
+    combine_intervals(a, b, |(i1, i2)| i1.sub(i2))
+}
+
+fn mul_intervals(a: &[Interval], b: &[Interval]) -> Vec<Interval> {
+    combine_intervals(a, b, |(i1, i2)| i1.mul(i2))
+}
+
+fn div_intervals(a: &[Interval], b: &[Interval]) -> Vec<Interval> {
+    combine_intervals(a, b, |(i1, i2)| i1.div(i2))
+}
+
+fn constant(c: f32) -> Vec<Interval> {
+    vec![Interval::new(c, c)]
+}
+
+fn sum_partial_intervals(
+    a: Option<Vec<Interval>>,
+    b: Option<Vec<Interval>>,
+) -> Option<Vec<Interval>> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(add_intervals(&a, &b)),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+fn difference_partial_intervals(
+    a: Option<Vec<Interval>>,
+    b: Option<Vec<Interval>>,
+) -> Option<Vec<Interval>> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(sub_intervals(&a, &b)),
+        (a, None) => a,
+        (None, Some(b)) => Some(sub_intervals(&constant(0.0), &b)),
+    }
+}
+
 impl Node {
     pub fn evaluate(&self, bindings: &HashMap<char, f32>) -> f32 {
         match *self {
@@ -21,6 +145,19 @@ impl Node {
             Node::Mul(ref n1, ref n2) => n1.evaluate(&bindings) * n2.evaluate(&bindings),
             Node::Div(ref n1, ref n2) => n1.evaluate(&bindings) / n2.evaluate(&bindings),
             Node::Exp(ref n1, ref n2) => n1.evaluate(&bindings).powf(n2.evaluate(&bindings)),
+            Node::Ln(ref n) => n.evaluate(&bindings).ln(),
+            Node::Sin(ref n) => n.evaluate(&bindings).sin(),
+            Node::Cos(ref n) => n.evaluate(&bindings).cos(),
+            Node::Abs(ref n) => n.evaluate(&bindings).abs(),
+            Node::Noise(order, ref noise, ref n1, ref n2, ref n3) => noise_derivative(
+                noise.permutation(),
+                order,
+                Vector3::new(
+                    n1.evaluate(&bindings),
+                    n2.evaluate(&bindings),
+                    n3.evaluate(&bindings),
+                ),
+            ),
             Node::Constant(c) => c,
             Node::Variable(v) => bindings.get(&v).unwrap().clone(),
         }
@@ -53,30 +190,427 @@ impl Node {
                     interval1.div(interval2)
                 })
             }
+            Node::Ln(ref n) => merge_intervals(
+                n.evaluate_intervals(&bindings)
+                    .iter()
+                    .flat_map(|interval| interval.ln())
+                    .collect(),
+            ),
+            Node::Sin(ref n) => vec![hull(&n.evaluate_intervals(&bindings)).sin()],
+            Node::Cos(ref n) => vec![hull(&n.evaluate_intervals(&bindings)).cos()],
+            Node::Abs(ref n) => merge_intervals(
+                n.evaluate_intervals(&bindings)
+                    .iter()
+                    .map(|interval| interval.abs())
+                    .collect(),
+            ),
+            Node::Noise(order, ref noise, ref n1, ref n2, ref n3) => {
+                vec![noise_derivative_interval(
+                    noise.permutation(),
+                    order,
+                    hull(&n1.evaluate_intervals(&bindings)),
+                    hull(&n2.evaluate_intervals(&bindings)),
+                    hull(&n3.evaluate_intervals(&bindings)),
+                )]
+            }
             Node::Constant(c) => vec![Interval { min: c, max: c }],
-            Node::Variable(v) => vec![bindings.get(&v).unwrap().clone()],
+            Node::Variable(v) => vec![interval_binding(bindings, v)],
+        }
+    }
+
+    pub fn depends_on(&self, v: char) -> bool {
+        match *self {
+            Node::Add(ref n1, ref n2)
+            | Node::Sub(ref n1, ref n2)
+            | Node::Mul(ref n1, ref n2)
+            | Node::Div(ref n1, ref n2)
+            | Node::Exp(ref n1, ref n2) => n1.depends_on(v) || n2.depends_on(v),
+            Node::Ln(ref n) | Node::Sin(ref n) | Node::Cos(ref n) | Node::Abs(ref n) => {
+                n.depends_on(v)
+            }
+            Node::Noise(_, _, ref n1, ref n2, ref n3) => {
+                n1.depends_on(v) || n2.depends_on(v) || n3.depends_on(v)
+            }
+            Node::Constant(_) => false,
+            Node::Variable(w) => w == v,
+        }
+    }
+
+    // The value and gradient in one pass, following the same rules as
+    // `derivative` without building the derivative trees
+    pub fn evaluate_gradient(&self, bindings: &HashMap<char, f32>) -> (f32, Vector3<f32>) {
+        let (value, partials) = self.forward(bindings);
+        let [x, y, z] = partials;
+        (
+            value,
+            Vector3::new(x.unwrap_or(0.0), y.unwrap_or(0.0), z.unwrap_or(0.0)),
+        )
+    }
+
+    // Interval enclosures of the value and gradient, the same as evaluating
+    // `derivative` over the intervals
+    pub fn evaluate_gradient_intervals(
+        &self,
+        bindings: &HashMap<char, Interval>,
+    ) -> (Vec<Interval>, [Vec<Interval>; 3]) {
+        let (value, partials) = self.forward_intervals(bindings);
+        let [x, y, z] = partials;
+        let zero = || constant(0.0);
+        (
+            value,
+            [
+                x.unwrap_or_else(zero),
+                y.unwrap_or_else(zero),
+                z.unwrap_or_else(zero),
+            ],
+        )
+    }
+
+    fn forward(&self, bindings: &HashMap<char, f32>) -> (f32, Partials<f32>) {
+        match *self {
+            Node::Add(ref n1, ref n2) => {
+                let ((v1, d1), (v2, d2)) = (n1.forward(bindings), n2.forward(bindings));
+                (v1 + v2, zip_partials(d1, d2, sum_partial))
+            }
+            Node::Sub(ref n1, ref n2) => {
+                let ((v1, d1), (v2, d2)) = (n1.forward(bindings), n2.forward(bindings));
+                (v1 - v2, zip_partials(d1, d2, difference_partial))
+            }
+            Node::Mul(ref n1, ref n2) => {
+                let ((v1, d1), (v2, d2)) = (n1.forward(bindings), n2.forward(bindings));
+                let partials = zip_partials(d1, d2, |d1, d2| {
+                    sum_partial(d1.map(|d| d * v2), d2.map(|d| v1 * d))
+                });
+                (v1 * v2, partials)
+            }
+            Node::Div(ref n1, ref n2) => {
+                let ((v1, d1), (v2, d2)) = (n1.forward(bindings), n2.forward(bindings));
+                let partials = zip_partials(d1, d2, |d1, d2| {
+                    difference_partial(d1.map(|d| d * v2), d2.map(|d| v1 * d))
+                        .map(|n| n / (v2 * v2))
+                });
+                (v1 / v2, partials)
+            }
+            Node::Exp(ref n1, ref n2) => {
+                let ((v1, d1), (v2, d2)) = (n1.forward(bindings), n2.forward(bindings));
+                let value = v1.powf(v2);
+                let partials = zip_partials(d1, d2, |d1, d2| match d2 {
+                    // Power rule
+                    None => d1.map(|d| v2 * v1.powf(v2 - 1.0) * d),
+                    Some(d2) => sum_partial(Some(d2 * v1.ln()), d1.map(|d| v2 * d / v1))
+                        .map(|inner| value * inner),
+                });
+                (value, partials)
+            }
+            Node::Ln(ref n) => {
+                let (v, d) = n.forward(bindings);
+                (v.ln(), map_partials(d, |d| d / v))
+            }
+            Node::Sin(ref n) => {
+                let (v, d) = n.forward(bindings);
+                (v.sin(), map_partials(d, |d| v.cos() * d))
+            }
+            Node::Cos(ref n) => {
+                let (v, d) = n.forward(bindings);
+                (v.cos(), map_partials(d, |d| -1.0 * v.sin() * d))
+            }
+            Node::Abs(ref n) => {
+                let (v, d) = n.forward(bindings);
+                (v.abs(), map_partials(d, |d| v * d / v.abs()))
+            }
+            Node::Noise(order, ref noise, ref n1, ref n2, ref n3) => {
+                let arguments = [
+                    n1.forward(bindings),
+                    n2.forward(bindings),
+                    n3.forward(bindings),
+                ];
+                let p = Vector3::new(arguments[0].0, arguments[1].0, arguments[2].0);
+                let value = noise_derivative(noise.permutation(), order, p);
+                let mut partials = [None, None, None];
+                for (axis, &(_, ref d)) in arguments.iter().enumerate() {
+                    let mut partial = order;
+                    partial[axis] += 1;
+                    let slope = noise_derivative(noise.permutation(), partial, p);
+                    for v in 0..3 {
+                        partials[v] = sum_partial(partials[v], d[v].map(|d| slope * d));
+                    }
+                }
+                (value, partials)
+            }
+            Node::Constant(c) => (c, [None, None, None]),
+            Node::Variable(w) => {
+                let mut partials = [None, None, None];
+                for v in 0..3 {
+                    if AXES[v] == w {
+                        partials[v] = Some(1.0);
+                    }
+                }
+                (bindings.get(&w).unwrap().clone(), partials)
+            }
         }
     }
+
+    fn forward_intervals(
+        &self,
+        bindings: &HashMap<char, Interval>,
+    ) -> (Vec<Interval>, Partials<Vec<Interval>>) {
+        match *self {
+            Node::Add(ref n1, ref n2) => {
+                let (v1, d1) = n1.forward_intervals(bindings);
+                let (v2, d2) = n2.forward_intervals(bindings);
+                (
+                    add_intervals(&v1, &v2),
+                    zip_partials(d1, d2, sum_partial_intervals),
+                )
+            }
+            Node::Sub(ref n1, ref n2) => {
+                let (v1, d1) = n1.forward_intervals(bindings);
+                let (v2, d2) = n2.forward_intervals(bindings);
+                (
+                    sub_intervals(&v1, &v2),
+                    zip_partials(d1, d2, difference_partial_intervals),
+                )
+            }
+            Node::Mul(ref n1, ref n2) => {
+                let (v1, d1) = n1.forward_intervals(bindings);
+                let (v2, d2) = n2.forward_intervals(bindings);
+                let partials = zip_partials(d1, d2, |d1, d2| {
+                    sum_partial_intervals(
+                        d1.map(|d| mul_intervals(&d, &v2)),
+                        d2.map(|d| mul_intervals(&v1, &d)),
+                    )
+                });
+                (mul_intervals(&v1, &v2), partials)
+            }
+            Node::Div(ref n1, ref n2) => {
+                let (v1, d1) = n1.forward_intervals(bindings);
+                let (v2, d2) = n2.forward_intervals(bindings);
+                let square = mul_intervals(&v2, &v2);
+                let partials = zip_partials(d1, d2, |d1, d2| {
+                    difference_partial_intervals(
+                        d1.map(|d| mul_intervals(&d, &v2)),
+                        d2.map(|d| mul_intervals(&v1, &d)),
+                    )
+                    .map(|n| div_intervals(&n, &square))
+                });
+                (div_intervals(&v1, &v2), partials)
+            }
+            Node::Exp(ref n1, ref n2) => {
+                let (v1, d1) = n1.forward_intervals(bindings);
+                let (v2, d2) = n2.forward_intervals(bindings);
+                let value = combine_intervals(&v1, &v2, |(i1, i2)| i1.exp(i2));
+                let partials = zip_partials(d1, d2, |d1, d2| match d2 {
+                    // Power rule
+                    None => d1.map(|d| {
+                        let lowered = sub_intervals(&v2, &constant(1.0));
+                        let power = combine_intervals(&v1, &lowered, |(i1, i2)| i1.exp(i2));
+                        mul_intervals(&mul_intervals(&v2, &power), &d)
+                    }),
+                    Some(d2) => {
+                        let ln = merge_intervals(v1.iter().flat_map(|i| i.ln()).collect());
+                        sum_partial_intervals(
+                            Some(mul_intervals(&d2, &ln)),
+                            d1.map(|d| div_intervals(&mul_intervals(&v2, &d), &v1)),
+                        )
+                        .map(|inner| mul_intervals(&value, &inner))
+                    }
+                });
+                (value, partials)
+            }
+            Node::Ln(ref n) => {
+                let (v, d) = n.forward_intervals(bindings);
+                let value = merge_intervals(v.iter().flat_map(|i| i.ln()).collect());
+                (value, map_partials(d, |d| div_intervals(&d, &v)))
+            }
+            Node::Sin(ref n) => {
+                let (v, d) = n.forward_intervals(bindings);
+                let cos = vec![hull(&v).cos()];
+                (
+                    vec![hull(&v).sin()],
+                    map_partials(d, |d| mul_intervals(&cos, &d)),
+                )
+            }
+            Node::Cos(ref n) => {
+                let (v, d) = n.forward_intervals(bindings);
+                let negative_sin = mul_intervals(&constant(-1.0), &[hull(&v).sin()]);
+                (
+                    vec![hull(&v).cos()],
+                    map_partials(d, |d| mul_intervals(&negative_sin, &d)),
+                )
+            }
+            Node::Abs(ref n) => {
+                let (v, d) = n.forward_intervals(bindings);
+                let value = merge_intervals(v.iter().map(|i| i.abs()).collect());
+                let partials = map_partials(d, |d| div_intervals(&mul_intervals(&v, &d), &value));
+                (value, partials)
+            }
+            Node::Noise(order, ref noise, ref n1, ref n2, ref n3) => {
+                let arguments = [
+                    n1.forward_intervals(bindings),
+                    n2.forward_intervals(bindings),
+                    n3.forward_intervals(bindings),
+                ];
+                let (x, y, z) = (
+                    hull(&arguments[0].0),
+                    hull(&arguments[1].0),
+                    hull(&arguments[2].0),
+                );
+                let bound = |order| {
+                    vec![noise_derivative_interval(
+                        noise.permutation(),
+                        order,
+                        x,
+                        y,
+                        z,
+                    )]
+                };
+                let value = bound(order);
+                let mut partials = [None, None, None];
+                for (axis, &(_, ref d)) in arguments.iter().enumerate() {
+                    let mut partial = order;
+                    partial[axis] += 1;
+                    let slope = bound(partial);
+                    for v in 0..3 {
+                        let term = d[v].as_ref().map(|d| mul_intervals(&slope, d));
+                        partials[v] = sum_partial_intervals(partials[v].take(), term);
+                    }
+                }
+                (value, partials)
+            }
+            Node::Constant(c) => (constant(c), [None, None, None]),
+            Node::Variable(w) => {
+                let mut partials = [None, None, None];
+                for v in 0..3 {
+                    if AXES[v] == w {
+                        partials[v] = Some(constant(1.0));
+                    }
+                }
+                (vec![interval_binding(bindings, w)], partials)
+            }
+        }
+    }
+
+    // The symbolic partial derivative with respect to v
+    pub fn derivative(&self, v: char) -> Node {
+        match *self {
+            Node::Add(ref n1, ref n2) => sum(n1.derivative(v), n2.derivative(v)),
+            Node::Sub(ref n1, ref n2) => difference(n1.derivative(v), n2.derivative(v)),
+            Node::Mul(ref n1, ref n2) => sum(
+                product(n1.derivative(v), *n2.clone()),
+                product(*n1.clone(), n2.derivative(v)),
+            ),
+            Node::Div(ref n1, ref n2) => quotient(
+                difference(
+                    product(n1.derivative(v), *n2.clone()),
+                    product(*n1.clone(), n2.derivative(v)),
+                ),
+                product(*n2.clone(), *n2.clone()),
+            ),
+            // Power rule, when the exponent doesn't vary with v
+            Node::Exp(ref n1, ref n2) if !n2.depends_on(v) => product(
+                product(
+                    *n2.clone(),
+                    Node::Exp(
+                        n1.clone(),
+                        Box::new(difference(*n2.clone(), Node::Constant(1.0))),
+                    ),
+                ),
+                n1.derivative(v),
+            ),
+            // d(u^w) = u^w * (w' ln(u) + w u' / u)
+            Node::Exp(ref n1, ref n2) => product(
+                self.clone(),
+                sum(
+                    product(n2.derivative(v), Node::Ln(n1.clone())),
+                    quotient(product(*n2.clone(), n1.derivative(v)), *n1.clone()),
+                ),
+            ),
+            Node::Ln(ref n) => quotient(n.derivative(v), *n.clone()),
+            Node::Sin(ref n) => product(Node::Cos(n.clone()), n.derivative(v)),
+            Node::Cos(ref n) => product(
+                product(Node::Constant(-1.0), Node::Sin(n.clone())),
+                n.derivative(v),
+            ),
+            // u u' / |u|, undefined where u is zero
+            Node::Abs(ref n) => quotient(product(*n.clone(), n.derivative(v)), self.clone()),
+            // Chain rule through each argument
+            Node::Noise(order, ref noise, ref n1, ref n2, ref n3) => {
+                let arguments = [n1, n2, n3];
+                (0..3).fold(Node::Constant(0.0), |total, axis| {
+                    let mut partial = order;
+                    partial[axis] += 1;
+                    let slope =
+                        Node::Noise(partial, noise.clone(), n1.clone(), n2.clone(), n3.clone());
+                    sum(total, product(slope, arguments[axis].derivative(v)))
+                })
+            }
+            Node::Constant(_) => Node::Constant(0.0),
+            Node::Variable(w) if w == v => Node::Constant(1.0),
+            Node::Variable(_) => Node::Constant(0.0),
+        }
+    }
+
+    // f / |grad f|, which is the distance to the surface to first order
+    pub fn normalized(&self) -> Node {
+        let squares = AXES.iter().fold(Node::Constant(0.0), |total, &v| {
+            let d = self.derivative(v);
+            sum(total, product(d.clone(), d))
+        });
+        let length = Node::Exp(Box::new(squares), Box::new(Node::Constant(0.5)));
+        quotient(self.clone(), length)
+    }
+}
+
+// The time a bare Node is evaluated at. Expressions may use t, and outside
+// of an AtTime it is fixed here rather than left unbound.
+pub const DEFAULT_TIME: f32 = 0.0;
+
+fn point_bindings(x: f32, y: f32, z: f32) -> HashMap<char, f32> {
+    let mut bindings = HashMap::new();
+    bindings.insert('x', x);
+    bindings.insert('y', y);
+    bindings.insert('z', z);
+    bindings.insert('t', DEFAULT_TIME);
+    bindings
+}
+
+// A free t is DEFAULT_TIME here too, looked up rather than added to a copy
+// of the bindings
+fn interval_binding(bindings: &HashMap<char, Interval>, v: char) -> Interval {
+    match bindings.get(&v) {
+        Some(i) => i.clone(),
+        None if v == 't' => Interval::new(DEFAULT_TIME, DEFAULT_TIME),
+        None => panic!("Unbound variable {}", v),
+    }
 }
 
 impl Function for Node {
     fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
-        let mut bindings = HashMap::new();
-        bindings.insert('x', x);
-        bindings.insert('y', y);
-        bindings.insert('z', z);
-
-        self.evaluate(&bindings)
+        self.evaluate(&point_bindings(x, y, z))
     }
 
     fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
-        self.evaluate_intervals(&bindings)
+        self.evaluate_intervals(bindings)
+    }
+
+    fn gradient(&self, x: f32, y: f32, z: f32) -> Vector3<f32> {
+        self.evaluate_gradient(&point_bindings(x, y, z)).1
+    }
+
+    fn evaluate_gradient_interval(
+        &self,
+        bindings: &HashMap<char, Interval>,
+    ) -> [Vec<Interval>; 3] {
+        self.evaluate_gradient_intervals(bindings).1
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use cgmath::InnerSpace;
+    use function::ClosureFunction;
+    use noise::Perlin;
     use parser::parse_expression;
     use std::collections::HashMap;
     #[macro_use]
@@ -169,9 +703,201 @@ mod tests {
         assert_similiar!(result[0].min, -15.5);
         assert_similiar!(result[0].max, 15.5);
 
+        // The gap in 1/x keeps this cell from being a false positive
+        input = "1/x - y".chars().collect();
+        root = parse_expression(&input, 0).unwrap();
+        bindings.insert('x', Interval::new(-0.5, 0.5));
+        bindings.insert('y', Interval::new(0.0, 0.5));
+        result = root.evaluate_interval(&bindings);
+        assert_eq!(result.len(), 2);
+        assert!(!contains_zero(&result));
+
+        bindings.insert('y', Interval::new(1.5, 2.5));
+        result = root.evaluate_interval(&bindings);
+        assert!(contains_zero(&result));
+
         // TDOD add more tests once behaivor settles
     }
 
+    #[test]
+    fn test_noise() {
+        let input: Vec<char> = "noise(2 * x, y + 0.5, z) - 0.1".chars().collect();
+        let root = parse_expression(&input, 0).unwrap();
+        let perlin = Perlin::default();
+        let (x, y, z) = (0.3f32, 1.7f32, -2.2f32);
+        assert_similiar!(
+            Function::evaluate(&*root, x, y, z),
+            perlin.evaluate(2.0 * x, y + 0.5, z) - 0.1
+        );
+
+        // Chain rule through the arguments
+        let g = Function::gradient(&*root, x, y, z);
+        let expected = perlin.gradient(2.0 * x, y + 0.5, z);
+        assert_similiar!(g.x, 2.0 * expected.x);
+        assert_similiar!(g.y, expected.y);
+
+        // Second derivatives by differences, unbounded over boxes
+        let h = 1e-3;
+        let dxx = root.derivative('x').derivative('x');
+        let fd = (Function::gradient(&*root, x + h, y, z).x
+            - Function::gradient(&*root, x - h, y, z).x) / (2.0 * h);
+        assert!((Function::evaluate(&dxx, x, y, z) - fd).abs() < 0.5);
+
+        let mut bindings = HashMap::new();
+        bindings.insert('x', Interval::new(0.29, 0.31));
+        bindings.insert('y', Interval::new(1.69, 1.71));
+        bindings.insert('z', Interval::new(-2.21, -2.19));
+        let bound = hull(&root.evaluate_interval(&bindings));
+        assert!(bound.min <= -0.1 + perlin.evaluate(0.6, 2.2, -2.2));
+        assert!(bound.max >= -0.1 + perlin.evaluate(0.6, 2.2, -2.2));
+        assert!(bound.width() < 1.0);
+        let gradient = root.evaluate_gradient_interval(&bindings);
+        assert!(hull(&gradient[0]).max >= 17.0 && hull(&gradient[0]).max < 18.0);
+
+        // A fourth argument seeds the permutation
+        let input: Vec<char> = "noise(x, y, z, 7)".chars().collect();
+        let seeded = parse_expression(&input, 0).unwrap();
+        let perlin = Perlin::new(7);
+        assert_eq!(
+            Function::evaluate(&*seeded, x, y, z),
+            perlin.evaluate(x, y, z)
+        );
+        assert_similiar!(
+            Function::gradient(&*seeded, x, y, z).z,
+            perlin.gradient(x, y, z).z
+        );
+    }
+
+    #[test]
+    fn test_derivative() {
+        let mut bindings = HashMap::new();
+        bindings.insert('x', 1.13);
+        bindings.insert('y', 4.232);
+        bindings.insert('z', 2.0939);
+        let (x, y, z) = (1.13f32, 4.232f32, 2.0939f32);
+
+        let input: Vec<char> = "x^2 + y^2 + z^2 - 1".chars().collect();
+        let root = parse_expression(&input, 0).unwrap();
+        assert_similiar!(root.derivative('x').evaluate(&bindings), 2.0 * x);
+        assert_similiar!(root.derivative('y').evaluate(&bindings), 2.0 * y);
+        assert_similiar!(root.derivative('z').evaluate(&bindings), 2.0 * z);
+
+        let input: Vec<char> = "x * y / z".chars().collect();
+        let root = parse_expression(&input, 0).unwrap();
+        assert_similiar!(root.derivative('x').evaluate(&bindings), y / z);
+        assert_similiar!(root.derivative('z').evaluate(&bindings), -x * y / (z * z));
+
+        let input: Vec<char> = "y ^ x".chars().collect();
+        let root = parse_expression(&input, 0).unwrap();
+        assert_similiar!(
+            root.derivative('x').evaluate(&bindings),
+            y.powf(x) * y.ln()
+        );
+        assert_similiar!(
+            root.derivative('y').evaluate(&bindings),
+            x * y.powf(x - 1.0)
+        );
+
+        // Symbolic and finite difference gradients agree
+        let input: Vec<char> = "x * y / z".chars().collect();
+        let root = parse_expression(&input, 0).unwrap();
+        let g = Function::gradient(&*root, x, y, z);
+        assert_similiar!(g.x, y / z);
+        assert_similiar!(g.z, -x * y / (z * z));
+        let f: &dyn Function = &ClosureFunction::new(|x, y, z| x * y / z, |_: &HashMap<char, Interval>| vec![]);
+        assert!((f.gradient(x, y, z) - g).magnitude() < 1e-2);
+
+        // Constants fold away entirely
+        let input: Vec<char> = "3 * x + 2".chars().collect();
+        let root = parse_expression(&input, 0).unwrap();
+        assert_eq!(root.derivative('x'), Node::Constant(3.0));
+        assert_eq!(root.derivative('y'), Node::Constant(0.0));
+    }
+
+    #[test]
+    fn test_forward_matches_derivative() {
+        let expressions = [
+            "x^2 + y^2 + z^2 - 1",
+            "x * y / z - y / x",
+            "y ^ x + z ^ 3",
+            "sin(2 * x) * cos(y) + abs(z - 0.5)",
+            "noise(x, 2 * y, z + x) - 0.1",
+        ];
+        let mut points = HashMap::new();
+        points.insert('x', 1.13);
+        points.insert('y', 4.232);
+        points.insert('z', 2.0939);
+        let mut boxes = HashMap::new();
+        boxes.insert('x', Interval::new(1.1, 1.2));
+        boxes.insert('y', Interval::new(4.2, 4.3));
+        boxes.insert('z', Interval::new(2.0, 2.1));
+
+        for expression in expressions.iter() {
+            let input: Vec<char> = expression.chars().collect();
+            let root = parse_expression(&input, 0).unwrap();
+
+            let (value, gradient) = root.evaluate_gradient(&points);
+            assert_similiar!(value, root.evaluate(&points));
+            let (values, gradients) = root.evaluate_gradient_intervals(&boxes);
+            let expected = hull(&root.evaluate_intervals(&boxes));
+            assert_eq!(
+                (hull(&values).min, hull(&values).max),
+                (expected.min, expected.max)
+            );
+            for (axis, &v) in ['x', 'y', 'z'].iter().enumerate() {
+                let derivative = root.derivative(v);
+                assert_similiar!(gradient[axis], derivative.evaluate(&points));
+                let expected = hull(&derivative.evaluate_intervals(&boxes));
+                let bound = hull(&gradients[axis]);
+                assert!((bound.min - expected.min).abs() < 1e-4);
+                assert!((bound.max - expected.max).abs() < 1e-4);
+            }
+        }
+
+        // No 0 / 0 from partials of ln(y) along x at y = 0
+        let input: Vec<char> = "x^2".chars().collect();
+        let square = parse_expression(&input, 0).unwrap();
+        let root = Node::Add(square, Box::new(Node::Ln(Box::new(Node::Variable('y')))));
+        points.insert('y', 0.0);
+        assert_similiar!(root.evaluate_gradient(&points).1.x, 2.0 * 1.13);
+    }
+
+    #[test]
+    fn test_free_time() {
+        // Outside an AtTime, t is DEFAULT_TIME rather than unbound
+        let input: Vec<char> = "x - t * y".chars().collect();
+        let root = parse_expression(&input, 0).unwrap();
+        assert_eq!(Function::evaluate(&*root, 0.5, 2.0, 0.0), 0.5);
+        assert_eq!(Function::gradient(&*root, 0.5, 2.0, 0.0).y, 0.0);
+
+        let mut bindings = HashMap::new();
+        bindings.insert('x', Interval::new(0.0, 1.0));
+        bindings.insert('y', Interval::new(-1.0, 1.0));
+        bindings.insert('z', Interval::new(-1.0, 1.0));
+        let bound = hull(&Function::evaluate_interval(&*root, &bindings));
+        assert!(bound.min == 0.0 && bound.max == 1.0);
+        let gradient = Function::evaluate_gradient_interval(&*root, &bindings);
+        assert!(hull(&gradient[0]).min == 1.0 && hull(&gradient[0]).max == 1.0);
+
+        // An explicit binding still wins
+        bindings.insert('t', Interval::new(1.0, 1.0));
+        let bound = hull(&Function::evaluate_interval(&*root, &bindings));
+        assert!(bound.min == -1.0 && bound.max == 2.0);
+    }
+
+    #[test]
+    fn test_negative_base_power() {
+        // x^2 = 2 at y = 0, so the cell has a root and mustn't evaluate empty
+        let input: Vec<char> = "x ^ (y + 2) - 2".chars().collect();
+        let root = parse_expression(&input, 0).unwrap();
+        let mut bindings = HashMap::new();
+        bindings.insert('x', Interval::new(-2.0, -1.0));
+        bindings.insert('y', Interval::new(-0.5, 0.5));
+        bindings.insert('z', Interval::new(0.0, 0.0));
+        let bound = hull(&Function::evaluate_interval(&*root, &bindings));
+        assert!(bound.contains_zero());
+    }
+
     fn test_function_inteval_2() {
         let mut input: Vec<char>;
         let mut root;
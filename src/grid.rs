@@ -0,0 +1,319 @@
+use cgmath::Vector3;
+use function::Function;
+use interval::Interval;
+use std::collections::HashMap;
+
+// Smallest and largest sample in each block of cells, with each level
+// pooling 2x2x2 blocks of the one below. Level 0 has one entry per cell.
+#[derive(Clone, Debug)]
+struct MinMaxLevel {
+    dims: [usize; 3],
+    min: Vec<f32>,
+    max: Vec<f32>,
+}
+
+impl MinMaxLevel {
+    fn index(&self, c: [usize; 3]) -> usize {
+        c[0] + self.dims[0] * (c[1] + self.dims[1] * c[2])
+    }
+
+    fn pool(&self) -> MinMaxLevel {
+        let dims = [
+            (self.dims[0] + 1) / 2,
+            (self.dims[1] + 1) / 2,
+            (self.dims[2] + 1) / 2,
+        ];
+        let mut level = MinMaxLevel {
+            dims,
+            min: vec![::std::f32::INFINITY; dims[0] * dims[1] * dims[2]],
+            max: vec![::std::f32::NEG_INFINITY; dims[0] * dims[1] * dims[2]],
+        };
+
+        for z in 0..self.dims[2] {
+            for y in 0..self.dims[1] {
+                for x in 0..self.dims[0] {
+                    let (from, to) = (self.index([x, y, z]), level.index([x / 2, y / 2, z / 2]));
+                    level.min[to] = level.min[to].min(self.min[from]);
+                    level.max[to] = level.max[to].max(self.max[from]);
+                }
+            }
+        }
+
+        level
+    }
+}
+
+// A scalar field sampled on a regular grid, x varying fastest in `values`.
+// Between samples it's interpolated trilinearly, and outside the grid the
+// boundary values carry on outwards.
+#[derive(Clone, Debug)]
+pub struct Grid {
+    dims: [usize; 3],
+    origin: Vector3<f32>,
+    spacing: Vector3<f32>,
+    values: Vec<f32>,
+    pyramid: Vec<MinMaxLevel>,
+}
+
+impl Grid {
+    pub fn new(
+        dims: [usize; 3],
+        origin: Vector3<f32>,
+        spacing: Vector3<f32>,
+        values: Vec<f32>,
+    ) -> Grid {
+        assert!(
+            dims.iter().all(|&d| d >= 2),
+            "Grid needs at least two samples along each axis"
+        );
+        assert!(
+            spacing.x > 0.0 && spacing.y > 0.0 && spacing.z > 0.0,
+            "Grid spacing must be positive"
+        );
+        assert_eq!(values.len(), dims[0] * dims[1] * dims[2]);
+
+        let mut grid = Grid {
+            dims,
+            origin,
+            spacing,
+            values,
+            pyramid: Vec::new(),
+        };
+        grid.build_pyramid();
+        grid
+    }
+
+    // Samples f at every grid point
+    pub fn sample<F: Function + ?Sized>(
+        f: &F,
+        dims: [usize; 3],
+        origin: Vector3<f32>,
+        spacing: Vector3<f32>,
+    ) -> Grid {
+        let mut values = Vec::with_capacity(dims[0] * dims[1] * dims[2]);
+        for z in 0..dims[2] {
+            for y in 0..dims[1] {
+                for x in 0..dims[0] {
+                    values.push(f.evaluate(
+                        origin.x + x as f32 * spacing.x,
+                        origin.y + y as f32 * spacing.y,
+                        origin.z + z as f32 * spacing.z,
+                    ));
+                }
+            }
+        }
+        Grid::new(dims, origin, spacing, values)
+    }
+
+    fn value(&self, x: usize, y: usize, z: usize) -> f32 {
+        self.values[x + self.dims[0] * (y + self.dims[1] * z)]
+    }
+
+    fn build_pyramid(&mut self) {
+        let dims = [self.dims[0] - 1, self.dims[1] - 1, self.dims[2] - 1];
+        let mut base = MinMaxLevel {
+            dims,
+            min: Vec::with_capacity(dims[0] * dims[1] * dims[2]),
+            max: Vec::with_capacity(dims[0] * dims[1] * dims[2]),
+        };
+
+        for z in 0..dims[2] {
+            for y in 0..dims[1] {
+                for x in 0..dims[0] {
+                    let mut min = ::std::f32::INFINITY;
+                    let mut max = ::std::f32::NEG_INFINITY;
+                    for i in 0..8 {
+                        let v = self.value(x + (i & 1), y + (i >> 1 & 1), z + (i >> 2));
+                        min = min.min(v);
+                        max = max.max(v);
+                    }
+                    base.min.push(min);
+                    base.max.push(max);
+                }
+            }
+        }
+
+        self.pyramid = vec![base];
+        while self.pyramid.last().unwrap().dims != [1, 1, 1] {
+            let next = self.pyramid.last().unwrap().pool();
+            self.pyramid.push(next);
+        }
+    }
+
+    // Position in grid units along an axis, clamped to the grid
+    fn grid_coordinate(&self, p: f32, axis: usize) -> f32 {
+        let u = (p - self.origin[axis]) / self.spacing[axis];
+        u.max(0.0).min((self.dims[axis] - 1) as f32)
+    }
+
+    fn cell(&self, u: f32, axis: usize) -> usize {
+        (u.floor() as usize).min(self.dims[axis] - 2)
+    }
+
+    // Min and max over the cells from lo to hi inclusive, descending from
+    // the block `c` at `level` and only splitting blocks that straddle the
+    // edge of the range
+    fn query(&self, level: usize, c: [usize; 3], lo: [usize; 3], hi: [usize; 3]) -> (f32, f32) {
+        let size = 1 << level;
+        let mut contained = true;
+        for axis in 0..3 {
+            let (start, end) = (c[axis] * size, (c[axis] + 1) * size - 1);
+            if start > hi[axis] || end < lo[axis] {
+                return (::std::f32::INFINITY, ::std::f32::NEG_INFINITY);
+            }
+            contained &= start >= lo[axis] && end <= hi[axis];
+        }
+
+        let layer = &self.pyramid[level];
+        if contained || level == 0 {
+            let i = layer.index(c);
+            return (layer.min[i], layer.max[i]);
+        }
+
+        let below = &self.pyramid[level - 1];
+        let mut result = (::std::f32::INFINITY, ::std::f32::NEG_INFINITY);
+        for i in 0..8 {
+            let child = [c[0] * 2 + (i & 1), c[1] * 2 + (i >> 1 & 1), c[2] * 2 + (i >> 2)];
+            if (0..3).any(|axis| child[axis] >= below.dims[axis]) {
+                continue;
+            }
+            let (min, max) = self.query(level - 1, child, lo, hi);
+            result = (result.0.min(min), result.1.max(max));
+        }
+        result
+    }
+}
+
+impl Function for Grid {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        let u = [
+            self.grid_coordinate(x, 0),
+            self.grid_coordinate(y, 1),
+            self.grid_coordinate(z, 2),
+        ];
+        let c = [self.cell(u[0], 0), self.cell(u[1], 1), self.cell(u[2], 2)];
+        let t = [u[0] - c[0] as f32, u[1] - c[1] as f32, u[2] - c[2] as f32];
+
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let along_x = |y: usize, z: usize| {
+            lerp(self.value(c[0], y, z), self.value(c[0] + 1, y, z), t[0])
+        };
+        let along_y = |z: usize| lerp(along_x(c[1], z), along_x(c[1] + 1, z), t[1]);
+        lerp(along_y(c[2]), along_y(c[2] + 1), t[2])
+    }
+
+    // Trilinear interpolation stays between the corners of its cell, so the
+    // samples of every cell the box touches bound it
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        let b = [bindings[&'x'], bindings[&'y'], bindings[&'z']];
+        if b.iter().any(|i| i.is_empty()) {
+            return vec![Interval::empty()];
+        }
+
+        let mut lo = [0; 3];
+        let mut hi = [0; 3];
+        for axis in 0..3 {
+            lo[axis] = self.cell(self.grid_coordinate(b[axis].min, axis), axis);
+            hi[axis] = self.cell(self.grid_coordinate(b[axis].max, axis), axis);
+        }
+
+        let (min, max) = self.query(self.pyramid.len() - 1, [0, 0, 0], lo, hi);
+        vec![Interval::widened(min, max)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mesh_tree::MeshTree;
+    use sdf::Sphere;
+    use test_util::{assert_bounds_within, cell};
+
+    fn sphere_grid() -> Grid {
+        let sphere = Sphere::new(Vector3::new(0.1, 0.0, -0.1), 0.8);
+        Grid::sample(
+            &sphere,
+            [11, 9, 13],
+            Vector3::new(-1.0, -1.0, -1.2),
+            Vector3::new(0.2, 0.25, 0.2),
+        )
+    }
+
+    #[test]
+    fn trilinear() {
+        // f = x + 2y + 3z is reproduced exactly
+        let mut values = Vec::new();
+        for z in 0..3 {
+            for y in 0..3 {
+                for x in 0..3 {
+                    values.push(x as f32 + 2.0 * y as f32 + 3.0 * z as f32);
+                }
+            }
+        }
+        let grid = Grid::new(
+            [3, 3, 3],
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 1.0),
+            values,
+        );
+        assert_similiar!(grid.evaluate(1.0, 1.0, 1.0), 6.0);
+        assert_similiar!(grid.evaluate(0.5, 1.25, 1.75), 8.25);
+        assert_similiar!(grid.evaluate(2.0, 2.0, 2.0), 12.0);
+
+        // Outside, the boundary carries on
+        assert_similiar!(grid.evaluate(5.0, 0.0, 0.0), 2.0);
+        assert_similiar!(grid.evaluate(-1.0, -1.0, 1.0), 3.0);
+    }
+
+    #[test]
+    fn pyramid_matches_cells() {
+        let grid = sphere_grid();
+        assert_eq!(grid.pyramid.last().unwrap().dims, [1, 1, 1]);
+
+        let base = &grid.pyramid[0];
+        let ranges = [([0, 0, 0], [9, 7, 11]), ([3, 1, 4], [6, 6, 4]), ([9, 0, 2], [9, 7, 8])];
+        for &(lo, hi) in &ranges {
+            let mut expected = (::std::f32::INFINITY, ::std::f32::NEG_INFINITY);
+            for z in lo[2]..hi[2] + 1 {
+                for y in lo[1]..hi[1] + 1 {
+                    for x in lo[0]..hi[0] + 1 {
+                        let i = base.index([x, y, z]);
+                        expected = (expected.0.min(base.min[i]), expected.1.max(base.max[i]));
+                    }
+                }
+            }
+            assert_eq!(grid.query(grid.pyramid.len() - 1, [0, 0, 0], lo, hi), expected);
+        }
+    }
+
+    #[test]
+    fn interval_bounds() {
+        let grid = sphere_grid();
+        let boxes = [
+            cell((-2.0, 2.0), (-2.0, 2.0), (-2.0, 2.0)),
+            cell((0.05, 0.3), (-0.1, 0.4), (0.0, 0.15)),
+            cell((0.61, 0.62), (0.1, 0.2), (-0.5, -0.4)),
+            cell((1.5, 3.0), (-0.3, 0.3), (0.2, 0.9)),
+        ];
+        for bb in &boxes {
+            assert_bounds_within(&grid, bb, 0.0);
+        }
+
+        // A small box away from the surface is excluded
+        let inside = cell((0.0, 0.1), (0.0, 0.1), (-0.1, 0.0));
+        assert!(!inside.contains_root(&Box::new(grid.clone())));
+    }
+
+    #[test]
+    fn mesh_grid() {
+        let grid = sphere_grid();
+        let bb = cell((-1.0, 1.0), (-1.0, 1.0), (-1.2, 1.2));
+        let mut tree = MeshTree::new(Box::new(grid), bb);
+        for _ in 0..4 {
+            tree.next_level().unwrap();
+        }
+        tree.generate_vertex_map();
+        tree.generate_triangle_set();
+        assert_eq!(tree.level, 4);
+    }
+}
@@ -1,5 +1,6 @@
 use function::Function;
 use itertools::Itertools;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::f32;
 
@@ -9,76 +10,443 @@ pub struct Interval {
     pub max: f32,
 }
 
+// The smallest float greater than v. Infinities and NaN are returned as is.
+pub fn next_up(v: f32) -> f32 {
+    if v.is_nan() || v == f32::INFINITY {
+        return v;
+    }
+
+    // Covers both 0.0 and -0.0
+    if v == 0.0 {
+        return f32::from_bits(1);
+    }
+
+    let bits = v.to_bits();
+    if v > 0.0 {
+        f32::from_bits(bits + 1)
+    } else {
+        f32::from_bits(bits - 1)
+    }
+}
+
+// The largest float less than v. Infinities and NaN are returned as is.
+pub fn next_down(v: f32) -> f32 {
+    if v == f32::NEG_INFINITY {
+        return v;
+    }
+
+    -next_up(-v)
+}
+
+// Directed rounding. `err` is the exact result minus the rounded one, or
+// anything with the same sign. A NaN error means the exact value could not be
+// recovered (overflow, infinite operands) so we step anyway to stay safe.
+//
+// Without the `rigorous` feature these are plain round-to-nearest.
+fn round_down(result: f32, err: f32) -> f32 {
+    if cfg!(feature = "rigorous") && (err < 0.0 || err.is_nan()) {
+        next_down(result)
+    } else {
+        result
+    }
+}
+
+fn round_up(result: f32, err: f32) -> f32 {
+    if cfg!(feature = "rigorous") && (err > 0.0 || err.is_nan()) {
+        next_up(result)
+    } else {
+        result
+    }
+}
+
+// Knuth's two-sum, the exact error of a + b
+fn add_err(a: f32, b: f32, sum: f32) -> f32 {
+    let b_virtual = sum - a;
+    let a_virtual = sum - b_virtual;
+    (a - a_virtual) + (b - b_virtual)
+}
+
+fn mul_err(a: f32, b: f32, product: f32) -> f32 {
+    a.mul_add(b, -product)
+}
+
+fn div_err(a: f32, b: f32, quotient: f32) -> f32 {
+    // a - q * b is exact, and the true quotient is q + (a - q * b) / b
+    let remainder = (-quotient).mul_add(b, a);
+    if remainder == 0.0 {
+        remainder
+    } else {
+        remainder.signum() * b.signum()
+    }
+}
+
+fn add_down(a: f32, b: f32) -> f32 {
+    let sum = a + b;
+    round_down(sum, add_err(a, b, sum))
+}
+
+fn add_up(a: f32, b: f32) -> f32 {
+    let sum = a + b;
+    round_up(sum, add_err(a, b, sum))
+}
+
+fn mul_down(a: f32, b: f32) -> f32 {
+    let product = a * b;
+    round_down(product, mul_err(a, b, product))
+}
+
+fn mul_up(a: f32, b: f32) -> f32 {
+    let product = a * b;
+    round_up(product, mul_err(a, b, product))
+}
+
+fn div_down(a: f32, b: f32) -> f32 {
+    let quotient = a / b;
+    round_down(quotient, div_err(a, b, quotient))
+}
+
+fn div_up(a: f32, b: f32) -> f32 {
+    let quotient = a / b;
+    round_up(quotient, div_err(a, b, quotient))
+}
+
 impl Interval {
     pub fn new(min: f32, max: f32) -> Interval {
         Interval { min, max }
     }
 
+    // The range of a function over a domain where it is undefined
+    pub fn empty() -> Interval {
+        Interval {
+            min: f32::INFINITY,
+            max: f32::NEG_INFINITY,
+        }
+    }
+
+    pub fn entire() -> Interval {
+        Interval {
+            min: f32::NEG_INFINITY,
+            max: f32::INFINITY,
+        }
+    }
+
+    // Anything with min > max is empty, as is anything with a NaN bound (a
+    // NaN constant is undefined everywhere).
+    pub fn is_empty(&self) -> bool {
+        !(self.min <= self.max)
+    }
+
+    // A NaN bound coming out of an operation on non-empty intervals (inf - inf
+    // and friends) means the bound was lost, not that the result is empty, so
+    // it becomes the matching infinity.
+    fn from_bounds(min: f32, max: f32) -> Interval {
+        Interval {
+            min: if min.is_nan() { f32::NEG_INFINITY } else { min },
+            max: if max.is_nan() { f32::INFINITY } else { max },
+        }
+    }
+
+    // For bounds from functions that are not correctly rounded (powf and
+    // friends). With the `rigorous` feature both bounds are pushed out by an
+    // ulp.
+    pub fn widened(min: f32, max: f32) -> Interval {
+        if cfg!(feature = "rigorous") {
+            Interval {
+                min: next_down(min),
+                max: next_up(max),
+            }
+        } else {
+            Interval { min, max }
+        }
+    }
+
     pub fn add(&self, other: &Interval) -> Vec<Interval> {
-        vec![Interval {
-            min: self.min + other.min,
-            max: self.max + other.max,
-        }]
+        if self.is_empty() || other.is_empty() {
+            return vec![Interval::empty()];
+        }
+
+        vec![Interval::from_bounds(
+            add_down(self.min, other.min),
+            add_up(self.max, other.max),
+        )]
     }
 
     pub fn sub(&self, other: &Interval) -> Vec<Interval> {
-        vec![Interval {
-            min: self.min - other.max,
-            max: self.max - other.min,
-        }]
+        if self.is_empty() || other.is_empty() {
+            return vec![Interval::empty()];
+        }
+
+        vec![Interval::from_bounds(
+            add_down(self.min, -other.max),
+            add_up(self.max, -other.min),
+        )]
     }
 
     pub fn mul(&self, other: &Interval) -> Vec<Interval> {
-        let minmax = [self.min, self.max]
+        if self.is_empty() || other.is_empty() {
+            return vec![Interval::empty()];
+        }
+
+        // A zero bound times an infinite one is NaN, but every real in the
+        // other interval times zero is zero, so that's what the corner is.
+        let zero_if_nan = |v: f32| if v.is_nan() { 0.0 } else { v };
+        let corners = [self.min, self.max]
             .iter()
             .cartesian_product(&[other.min, other.max])
-            .map(|(min, max)| min * max)
-            .minmax()
-            .into_option()
-            .unwrap();
+            .map(|(a, b)| (zero_if_nan(mul_down(*a, *b)), zero_if_nan(mul_up(*a, *b))))
+            .collect::<Vec<(f32, f32)>>();
 
-        vec![Interval {
-            min: minmax.0,
-            max: minmax.1,
-        }]
+        vec![Interval::from_bounds(
+            corners.iter().map(|c| c.0).fold(f32::INFINITY, f32::min),
+            corners.iter().map(|c| c.1).fold(f32::NEG_INFINITY, f32::max),
+        )]
     }
 
+    // Extended (Kahan) division. When the divisor straddles zero the quotient
+    // is the union of two half-infinite intervals, rather than the whole line.
     pub fn div(&self, other: &Interval) -> Vec<Interval> {
-        let inverse = match (other.min, other.max) {
-            (_, _) if !other.contains_zero() => Interval {
-                min: 1.0 / other.min,
-                max: 1.0 / other.max,
-            },
-            (min, max) if max == 0.0 => Interval {
-                min: -f32::INFINITY,
-                max: 1.0 / min,
-            },
-            (min, max) if min == 0.0 => Interval {
-                min: 1.0 / max,
-                max: f32::INFINITY,
-            },
-            (min, max) => Interval {
-                min: -f32::INFINITY,
-                max: f32::INFINITY,
-            },
+        let (a, b) = (self.min, self.max);
+        let (c, d) = (other.min, other.max);
+
+        // Dividing by exactly zero is undefined everywhere
+        if self.is_empty() || other.is_empty() || c == 0.0 && d == 0.0 {
+            return vec![Interval::empty()];
+        }
+
+        if c > 0.0 || d < 0.0 {
+            let corners = [a, b]
+                .iter()
+                .cartesian_product(&[c, d])
+                .map(|(n, m)| (div_down(*n, *m), div_up(*n, *m)))
+                .collect::<Vec<(f32, f32)>>();
+
+            return vec![Interval::from_bounds(
+                corners.iter().map(|c| c.0).fold(f32::INFINITY, f32::min),
+                corners.iter().map(|c| c.1).fold(f32::NEG_INFINITY, f32::max),
+            )];
+        }
+
+        // The numerator can reach zero, so any quotient is possible
+        if a <= 0.0 && b >= 0.0 {
+            return vec![Interval::entire()];
+        }
+
+        // From here the divisor touches zero and the numerator is strictly
+        // on one side of it
+        let below = |max: f32| Interval {
+            min: f32::NEG_INFINITY,
+            max: max,
+        };
+        let above = |min: f32| Interval {
+            min: min,
+            max: f32::INFINITY,
         };
 
-        self.mul(&inverse)
+        if b < 0.0 {
+            match (c == 0.0, d == 0.0) {
+                (true, _) => vec![below(div_up(b, d))],
+                (_, true) => vec![above(div_down(b, c))],
+                _ => vec![below(div_up(b, d)), above(div_down(b, c))],
+            }
+        } else {
+            match (c == 0.0, d == 0.0) {
+                (true, _) => vec![above(div_down(a, d))],
+                (_, true) => vec![below(div_up(a, c))],
+                _ => vec![below(div_up(a, c)), above(div_down(a, d))],
+            }
+        }
     }
 
     pub fn exp(&self, power: &Interval) -> Vec<Interval> {
-        let minmax = [self.min, self.max]
+        if self.is_empty() || power.is_empty() {
+            return vec![Interval::empty()];
+        }
+
+        // A negative base is only defined at integer powers, where the sign
+        // alternates, so over a range of powers all that's known is the size
+        if self.min < 0.0 && power.min != power.max {
+            if self.contains_zero() && power.min < 0.0 {
+                return vec![Interval::entire()];
+            }
+            let low = if self.contains_zero() {
+                0.0
+            } else {
+                self.max.abs()
+            };
+            let high = self.min.abs().max(self.max.abs());
+            let size = [low, high]
+                .iter()
+                .cartesian_product(&[power.min, power.max])
+                .map(|(base, power)| base.powf(*power))
+                .fold(0.0, f32::max);
+            return vec![Interval::widened(-size, size)];
+        }
+
+        // Negative bases to fractional powers are NaN, those corners are
+        // outside the domain and don't contribute to the range
+        let values: Vec<f32> = [self.min, self.max]
             .iter()
             .cartesian_product(&[power.min, power.max])
             .map(|(base, power)| base.powf(*power))
-            .minmax()
-            .into_option()
-            .unwrap();
+            .filter(|v| !v.is_nan())
+            .collect();
 
-        vec![Interval {
-            min: if self.contains_zero() { 0.0 } else { minmax.0 },
-            max: minmax.1,
-        }]
+        if values.is_empty() {
+            return vec![Interval::empty()];
+        }
+
+        let mut min = values.iter().cloned().fold(f32::INFINITY, f32::min);
+        let mut max = values.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+
+        // The base passes through zero, where a positive power is zero and a
+        // negative one blows up
+        if self.contains_zero() {
+            if power.min < 0.0 {
+                return vec![Interval::entire()];
+            }
+            min = min.min(0.0);
+            max = max.max(0.0);
+        }
+
+        vec![Interval::widened(min, max)]
+    }
+
+    pub fn ln(&self) -> Vec<Interval> {
+        if self.is_empty() || self.max <= 0.0 {
+            return vec![Interval::empty()];
+        }
+
+        let min = if self.min <= 0.0 {
+            f32::NEG_INFINITY
+        } else {
+            self.min.ln()
+        };
+
+        vec![Interval::widened(min, self.max.ln())]
+    }
+
+    // The rest of these never split into unions, so they hand back a single
+    // interval rather than a Vec.
+
+    pub fn neg(&self) -> Interval {
+        Interval {
+            min: -self.max,
+            max: -self.min,
+        }
+    }
+
+    pub fn abs(&self) -> Interval {
+        if self.is_empty() || self.min >= 0.0 {
+            *self
+        } else if self.max <= 0.0 {
+            self.neg()
+        } else {
+            Interval {
+                min: 0.0,
+                max: self.max.max(-self.min),
+            }
+        }
+    }
+
+    // Tighter than self.mul(self), which can't tell both sides are the same
+    pub fn square(&self) -> Interval {
+        if self.is_empty() {
+            return *self;
+        }
+
+        let abs = self.abs();
+        Interval {
+            min: mul_down(abs.min, abs.min),
+            max: mul_up(abs.max, abs.max),
+        }
+    }
+
+    // Only the non-negative part is in the domain
+    pub fn sqrt(&self) -> Interval {
+        if self.is_empty() || self.max < 0.0 {
+            return Interval::empty();
+        }
+
+        Interval::widened(self.min.max(0.0).sqrt(), self.max.sqrt())
+    }
+
+    pub fn cos(&self) -> Interval {
+        if self.is_empty() {
+            return *self;
+        }
+
+        let tau = 2.0 * f32::consts::PI;
+        if !(self.width() < tau) {
+            return Interval::new(-1.0, 1.0);
+        }
+
+        let mut min = self.min.cos().min(self.max.cos());
+        let mut max = self.min.cos().max(self.max.cos());
+
+        // Peaks at even multiples of pi, troughs at odd ones. Our pi is only
+        // known to be within an ulp of the real one, so a multiple counts if
+        // it could be in the interval for any pi in that ulp.
+        let (pi_low, pi_high) = (next_down(f32::consts::PI) as f64, f32::consts::PI as f64);
+        let (low, high) = (self.min as f64, self.max as f64);
+        let first = (low / pi_low).min(low / pi_high).ceil();
+        let last = (high / pi_low).max(high / pi_high).floor();
+        let reaches = |odd: bool| first < last || (first == last && (first % 2.0 != 0.0) == odd);
+        if reaches(false) {
+            max = 1.0;
+        }
+        if reaches(true) {
+            min = -1.0;
+        }
+
+        // cos isn't correctly rounded
+        let widened = Interval::widened(min, max);
+        Interval::new(widened.min.max(-1.0), widened.max.min(1.0))
+    }
+
+    pub fn sin(&self) -> Interval {
+        // cos(x - pi/2), with the real pi/2 somewhere in the last ulp below
+        // ours
+        let half_pi = Interval::new(next_down(f32::consts::FRAC_PI_2), f32::consts::FRAC_PI_2);
+        hull(&self.sub(&half_pi)).cos()
+    }
+
+    pub fn minimum(&self, other: &Interval) -> Interval {
+        if self.is_empty() || other.is_empty() {
+            return Interval::empty();
+        }
+
+        Interval {
+            min: self.min.min(other.min),
+            max: self.max.min(other.max),
+        }
+    }
+
+    pub fn maximum(&self, other: &Interval) -> Interval {
+        if self.is_empty() || other.is_empty() {
+            return Interval::empty();
+        }
+
+        Interval {
+            min: self.min.max(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn intersect(&self, other: &Interval) -> Interval {
+        let result = Interval {
+            min: self.min.max(other.min),
+            max: self.max.min(other.max),
+        };
+
+        if result.is_empty() {
+            Interval::empty()
+        } else {
+            result
+        }
+    }
+
+    pub fn width(&self) -> f32 {
+        self.max - self.min
     }
 
     pub fn middle(&self) -> f32 {
@@ -99,8 +467,10 @@ impl Interval {
         ]
     }
 
+    // Intervals are closed, so a bound of exactly zero counts. Empty
+    // intervals contain nothing.
     pub fn contains_zero(&self) -> bool {
-        self.min <= 0.0 && self.max > 0.0
+        self.min <= 0.0 && self.max >= 0.0
     }
 
     pub fn clamp_value(&self, v: f32) -> f32 {
@@ -122,14 +492,62 @@ pub fn permute_intervals<A, F>(
 ) -> Vec<Interval>
 where
     F: FnMut((&Interval, &Interval)) -> Vec<Interval>,
-    A: Function,
+    A: Function + ?Sized,
 {
     let n1_i = node1.evaluate_interval(&bindings);
     let n2_i = node2.evaluate_interval(&bindings);
 
-    n1_i.iter().cartesian_product(&n2_i).map(op).concat()
+    combine_intervals(&n1_i, &n2_i, op)
 }
 
+// Applies a binary op to every pair of pieces from two unions
+pub fn combine_intervals<F>(intervals1: &[Interval], intervals2: &[Interval], op: F) -> Vec<Interval>
+where
+    F: FnMut((&Interval, &Interval)) -> Vec<Interval>,
+{
+    merge_intervals(intervals1.iter().cartesian_product(intervals2).map(op).concat())
+}
+
+// Coalesces overlapping pieces of a union, so repeated division doesn't grow
+// the number of intervals exponentially as they are permuted up the tree.
+// Empty pieces are dropped, so the empty set comes back as an empty Vec.
+pub fn merge_intervals(mut intervals: Vec<Interval>) -> Vec<Interval> {
+    intervals.retain(|interval| !interval.is_empty());
+    intervals.sort_by(|a, b| a.min.partial_cmp(&b.min).unwrap_or(Ordering::Equal));
+
+    let mut merged: Vec<Interval> = Vec::with_capacity(intervals.len());
+    for interval in intervals {
+        if let Some(last) = merged.last_mut() {
+            if interval.min <= last.max {
+                last.max = last.max.max(interval.max);
+                continue;
+            }
+        }
+        merged.push(interval);
+    }
+
+    merged
+}
+
+// The smallest single interval covering a union
+pub fn hull(intervals: &[Interval]) -> Interval {
+    intervals
+        .iter()
+        .filter(|interval| !interval.is_empty())
+        .fold(Interval::empty(), |acc, interval| Interval {
+            min: acc.min.min(interval.min),
+            max: acc.max.max(interval.max),
+        })
+}
+
+// Non empty with finite bounds, so it actually constrains the value
+pub fn is_bounded(intervals: &[Interval]) -> bool {
+    let h = hull(intervals);
+    !h.is_empty() && h.min.is_finite() && h.max.is_finite()
+}
+
+// The intervals are treated as a union, so a gap between two pieces of an
+// extended division result can exclude zero.
 pub fn contains_zero(intervals: &[Interval]) -> bool {
     for interval in intervals {
         if interval.contains_zero() {
@@ -191,4 +609,340 @@ mod tests {
         close(r[0].min, -2.0, f32::EPSILON);
         close(r[0].max, 2.0, f32::EPSILON);
     }
+
+    #[test]
+    fn test_div() {
+        let a = Interval::new(1.0, 2.0);
+        let b = Interval::new(2.0, 4.0);
+        let r = a.div(&b);
+        assert_eq!(r.len(), 1);
+        close(r[0].min, 0.25, f32::EPSILON);
+        close(r[0].max, 1.0, f32::EPSILON);
+
+        let a = Interval::new(1.0, 2.0);
+        let b = Interval::new(-4.0, -2.0);
+        let r = a.div(&b);
+        assert_eq!(r.len(), 1);
+        close(r[0].min, -1.0, f32::EPSILON);
+        close(r[0].max, -0.25, f32::EPSILON);
+
+        // Straddling divisor splits into two pieces
+        let a = Interval::new(1.0, 2.0);
+        let b = Interval::new(-1.0, 2.0);
+        let r = a.div(&b);
+        assert_eq!(r.len(), 2);
+        assert_eq!(r[0].min, f32::NEG_INFINITY);
+        close(r[0].max, -1.0, f32::EPSILON);
+        close(r[1].min, 0.5, f32::EPSILON);
+        assert_eq!(r[1].max, f32::INFINITY);
+        assert!(!contains_zero(&r));
+
+        let a = Interval::new(-2.0, -1.0);
+        let b = Interval::new(-1.0, 2.0);
+        let r = a.div(&b);
+        assert_eq!(r.len(), 2);
+        assert_eq!(r[0].min, f32::NEG_INFINITY);
+        close(r[0].max, -0.5, f32::EPSILON);
+        close(r[1].min, 1.0, f32::EPSILON);
+        assert_eq!(r[1].max, f32::INFINITY);
+
+        // Divisor touching zero at one end gives a single half-infinite piece
+        let a = Interval::new(1.0, 2.0);
+        let b = Interval::new(0.0, 2.0);
+        let r = a.div(&b);
+        assert_eq!(r.len(), 1);
+        close(r[0].min, 0.5, f32::EPSILON);
+        assert_eq!(r[0].max, f32::INFINITY);
+
+        let a = Interval::new(1.0, 2.0);
+        let b = Interval::new(-2.0, 0.0);
+        let r = a.div(&b);
+        assert_eq!(r.len(), 1);
+        assert_eq!(r[0].min, f32::NEG_INFINITY);
+        close(r[0].max, -0.5, f32::EPSILON);
+
+        // Numerator reaching zero can produce anything
+        let a = Interval::new(-1.0, 2.0);
+        let b = Interval::new(-1.0, 2.0);
+        let r = a.div(&b);
+        assert_eq!(r.len(), 1);
+        assert_eq!(r[0].min, f32::NEG_INFINITY);
+        assert_eq!(r[0].max, f32::INFINITY);
+    }
+
+    #[test]
+    fn test_merge_intervals() {
+        let r = merge_intervals(vec![
+            Interval::new(3.0, 4.0),
+            Interval::new(-1.0, 1.0),
+            Interval::new(0.5, 2.0),
+        ]);
+        assert_eq!(r.len(), 2);
+        close(r[0].min, -1.0, f32::EPSILON);
+        close(r[0].max, 2.0, f32::EPSILON);
+        close(r[1].min, 3.0, f32::EPSILON);
+        close(r[1].max, 4.0, f32::EPSILON);
+
+        let r = merge_intervals(vec![
+            Interval::new(f32::NEG_INFINITY, 0.0),
+            Interval::new(0.0, f32::INFINITY),
+        ]);
+        assert_eq!(r.len(), 1);
+    }
+
+    #[test]
+    fn test_contains_zero() {
+        assert!(Interval::new(-1.0, 1.0).contains_zero());
+        assert!(Interval::new(0.0, 1.0).contains_zero());
+        assert!(Interval::new(-1.0, 0.0).contains_zero());
+        assert!(Interval::new(0.0, 0.0).contains_zero());
+        assert!(!Interval::new(0.5, 1.0).contains_zero());
+        assert!(!Interval::new(-1.0, -0.5).contains_zero());
+        assert!(!Interval::empty().contains_zero());
+        assert!(!Interval::new(f32::NAN, f32::NAN).contains_zero());
+
+        assert!(!contains_zero(&[]));
+        assert!(contains_zero(&[
+            Interval::new(-2.0, -1.0),
+            Interval::new(0.0, 1.0),
+        ]));
+    }
+
+    #[test]
+    fn test_empty() {
+        let e = Interval::empty();
+        assert!(e.is_empty());
+        assert!(Interval::new(f32::NAN, 1.0).is_empty());
+        assert!(Interval::new(2.0, 1.0).is_empty());
+        assert!(!Interval::new(1.0, 1.0).is_empty());
+        assert!(!Interval::entire().is_empty());
+
+        let a = Interval::new(-1.0, 1.0);
+        assert!(a.add(&e)[0].is_empty());
+        assert!(e.sub(&a)[0].is_empty());
+        assert!(a.mul(&e)[0].is_empty());
+        assert!(e.div(&a)[0].is_empty());
+        assert!(a.exp(&e)[0].is_empty());
+
+        // Division by exactly zero is undefined
+        assert!(a.div(&Interval::new(0.0, 0.0))[0].is_empty());
+
+        assert!(merge_intervals(vec![e, e]).is_empty());
+        assert_eq!(merge_intervals(vec![e, a]).len(), 1);
+    }
+
+    #[test]
+    fn test_nan_bounds() {
+        // 0 * inf corners are zero, not NaN
+        let r = Interval::new(0.0, 1.0).mul(&Interval::new(1.0, f32::INFINITY));
+        close(r[0].min, 0.0, f32::EPSILON);
+        assert_eq!(r[0].max, f32::INFINITY);
+
+        let r = Interval::new(0.0, 0.0).mul(&Interval::entire());
+        close(r[0].min, 0.0, f32::EPSILON);
+        close(r[0].max, 0.0, f32::EPSILON);
+
+        // inf - inf loses the bound, which widens rather than empties
+        let inf = Interval::new(f32::INFINITY, f32::INFINITY);
+        let r = inf.sub(&inf);
+        assert!(!r[0].is_empty());
+        assert_eq!(r[0].min, f32::NEG_INFINITY);
+        assert_eq!(r[0].max, f32::INFINITY);
+    }
+
+    #[test]
+    fn test_exp() {
+        // Odd powers keep their sign through zero
+        let r = Interval::new(-2.0, 1.0).exp(&Interval::new(3.0, 3.0));
+        close(r[0].min, -8.0, 1e-5);
+        close(r[0].max, 1.0, 1e-5);
+
+        let r = Interval::new(-2.0, 1.0).exp(&Interval::new(2.0, 2.0));
+        close(r[0].min, 0.0, 1e-5);
+        close(r[0].max, 4.0, 1e-5);
+
+        // Only the non-negative part of the base is in the domain
+        let r = Interval::new(-1.0, 4.0).exp(&Interval::new(0.5, 0.5));
+        close(r[0].min, 0.0, 1e-5);
+        close(r[0].max, 2.0, 1e-5);
+
+        let r = Interval::new(-4.0, -1.0).exp(&Interval::new(0.5, 0.5));
+        assert!(r[0].is_empty());
+
+        // Integer powers in the range reach both signs
+        let r = Interval::new(-2.0, -1.0).exp(&Interval::new(1.5, 2.5));
+        close(r[0].min, -2.0f32.powf(2.5), 1e-5);
+        close(r[0].max, 2.0f32.powf(2.5), 1e-5);
+
+        let r = Interval::new(-2.0, 0.5).exp(&Interval::new(-1.0, 1.0));
+        assert_eq!(r[0].min, f32::NEG_INFINITY);
+        assert_eq!(r[0].max, f32::INFINITY);
+    }
+
+    #[test]
+    fn test_ln() {
+        let r = Interval::new(1.0, 2.0).ln();
+        close(r[0].min, 0.0, 1e-6);
+        close(r[0].max, 2.0f32.ln(), 1e-6);
+
+        let r = Interval::new(-1.0, 1.0).ln();
+        assert_eq!(r[0].min, f32::NEG_INFINITY);
+        close(r[0].max, 0.0, 1e-6);
+
+        assert!(Interval::new(-2.0, -1.0).ln()[0].is_empty());
+    }
+
+    #[test]
+    fn test_unary() {
+        let r = Interval::new(-3.0, 2.0).abs();
+        close(r.min, 0.0, f32::EPSILON);
+        close(r.max, 3.0, f32::EPSILON);
+        let r = Interval::new(-3.0, -2.0).abs();
+        close(r.min, 2.0, f32::EPSILON);
+        close(r.max, 3.0, f32::EPSILON);
+
+        let r = Interval::new(-3.0, 2.0).square();
+        close(r.min, 0.0, f32::EPSILON);
+        close(r.max, 9.0, f32::EPSILON);
+        let r = Interval::new(2.0, 3.0).square();
+        close(r.min, 4.0, f32::EPSILON);
+        close(r.max, 9.0, f32::EPSILON);
+
+        let r = Interval::new(-4.0, 9.0).sqrt();
+        close(r.min, 0.0, 1e-6);
+        close(r.max, 3.0, 1e-6);
+        assert!(Interval::new(-4.0, -1.0).sqrt().is_empty());
+
+        let a = Interval::new(-1.0, 2.0);
+        let b = Interval::new(0.0, 1.0);
+        let r = a.minimum(&b);
+        close(r.min, -1.0, f32::EPSILON);
+        close(r.max, 1.0, f32::EPSILON);
+        let r = a.maximum(&b);
+        close(r.min, 0.0, f32::EPSILON);
+        close(r.max, 2.0, f32::EPSILON);
+    }
+
+    #[test]
+    fn test_trig() {
+        let pi = f32::consts::PI;
+
+        let r = Interval::new(0.0, 1.0).cos();
+        close(r.min, 1.0f32.cos(), 1e-6);
+        close(r.max, 1.0, 1e-6);
+
+        let r = Interval::new(1.0, 4.0).cos();
+        close(r.min, -1.0, 1e-6);
+        close(r.max, 1.0f32.cos(), 1e-6);
+
+        let r = Interval::new(-10.0, 10.0).cos();
+        close(r.min, -1.0, 1e-6);
+        close(r.max, 1.0, 1e-6);
+
+        let r = Interval::new(0.0, pi / 2.0).sin();
+        close(r.min, 0.0, 1e-6);
+        close(r.max, 1.0, 1e-6);
+
+        let r = Interval::new(pi, 1.5 * pi).sin();
+        close(r.min, -1.0, 1e-6);
+        close(r.max, 0.0, 1e-6);
+
+        // Spot check against samples
+        for &(a, b) in &[(-7.3, -5.1), (2.0, 2.5), (11.0, 13.0), (-0.5, 0.3)] {
+            let r = Interval::new(a, b).sin();
+            for n in 0..101 {
+                let v = (a + (b - a) * n as f32 / 100.0).sin();
+                assert!(v >= r.min - 1e-6 && v <= r.max + 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn test_intersect_hull() {
+        let a = Interval::new(-1.0, 1.0);
+        let b = Interval::new(0.5, 2.0);
+        let r = a.intersect(&b);
+        close(r.min, 0.5, f32::EPSILON);
+        close(r.max, 1.0, f32::EPSILON);
+
+        assert!(a.intersect(&Interval::new(2.0, 3.0)).is_empty());
+
+        let r = hull(&[b, Interval::empty(), Interval::new(-3.0, -2.0)]);
+        close(r.min, -3.0, f32::EPSILON);
+        close(r.max, 2.0, f32::EPSILON);
+
+        assert!(hull(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_next_up_down() {
+        assert!(next_up(1.0) > 1.0);
+        assert!(next_down(1.0) < 1.0);
+        assert!(next_up(-1.0) > -1.0);
+        assert!(next_down(-1.0) < -1.0);
+        assert_eq!(next_down(next_up(1.0)), 1.0);
+        assert_eq!(next_up(next_down(-3.5)), -3.5);
+
+        assert!(next_up(0.0) > 0.0);
+        assert!(next_up(-0.0) > 0.0);
+        assert!(next_down(0.0) < 0.0);
+
+        assert_eq!(next_up(f32::INFINITY), f32::INFINITY);
+        assert_eq!(next_down(f32::NEG_INFINITY), f32::NEG_INFINITY);
+        assert_eq!(next_up(f32::MAX), f32::INFINITY);
+        assert!(next_up(f32::NAN).is_nan());
+    }
+
+    #[cfg(feature = "rigorous")]
+    #[test]
+    fn test_rigorous_trig() {
+        let pi = f32::consts::PI;
+
+        // Our pi isn't the real one, so sin(pi) isn't zero and cos doesn't
+        // quite peak at multiples of our tau
+        let exact = (pi as f64).sin();
+        let r = Interval::new(pi, pi).sin();
+        assert!(r.min as f64 <= exact && exact <= r.max as f64);
+        for &x in &[pi, 1000.0 * pi, 1e6 * pi] {
+            let exact = (x as f64).cos();
+            let r = Interval::new(x, x).cos();
+            assert!(r.min as f64 <= exact && exact <= r.max as f64);
+            let exact = (x as f64).sin();
+            let r = Interval::new(x, x).sin();
+            assert!(r.min as f64 <= exact && exact <= r.max as f64);
+        }
+    }
+
+    #[cfg(feature = "rigorous")]
+    #[test]
+    fn test_rigorous_rounding() {
+        // 1 + 1e-8 rounds to 1, so only the upper bound has to move
+        let a = Interval::new(1.0, 1.0);
+        let b = Interval::new(1e-8, 1e-8);
+        let r = a.add(&b);
+        assert_eq!(r[0].min, 1.0);
+        assert!(r[0].max > 1.0);
+
+        let r = a.sub(&b);
+        assert!(r[0].min < 1.0);
+        assert_eq!(r[0].max, 1.0);
+
+        // Compare against the exact results in f64
+        let third = 1.0f32 / 3.0;
+        let r = Interval::new(1.0, 3.0).mul(&Interval::new(third, third));
+        assert!(r[0].min as f64 <= third as f64);
+        assert!(r[0].max as f64 >= 3.0 * third as f64);
+
+        let r = Interval::new(1.0, 2.0).div(&Interval::new(3.0, 3.0));
+        assert!((r[0].min as f64) < 1.0 / 3.0);
+        assert!((r[0].max as f64) > 2.0 / 3.0);
+
+        // Exact results are left alone
+        let r = Interval::new(1.0, 2.0).add(&Interval::new(2.0, 3.0));
+        assert_eq!(r[0].min, 3.0);
+        assert_eq!(r[0].max, 5.0);
+        let r = Interval::new(1.0, 2.0).div(&Interval::new(4.0, 4.0));
+        assert_eq!(r[0].min, 0.25);
+        assert_eq!(r[0].max, 0.5);
+    }
 }
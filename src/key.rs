@@ -4,6 +4,9 @@ use std::hash::Hash;
 
 pub const COMPONENT_BIT_COUNT: u32 = 21;
 
+// MortonKey128 has room for twice the levels
+pub const WIDE_COMPONENT_BIT_COUNT: u32 = 42;
+
 const ISOLATE_COMPONENT_MASKS: [u64; 3] = [
     0b100100100100100100100100100100100100100100100100100100100100100,
     0b010010010010010010010010010010010010010010010010010010010010010,
@@ -96,11 +99,94 @@ impl Neighbor {
     }
 }
 
-pub trait Key: Hash + Sized + Copy + Clone + PartialEq + Eq {
+pub trait Key: Hash + Sized + Copy + Clone + PartialEq + Eq + Ord {
     fn root_key() -> Self;
     fn child_key(&self, i: u64) -> Self;
     fn level(&self) -> u32;
     fn neighbor_key(&self, Neighbor) -> Option<Self>;
+    // The deepest level a key can address. Children of keys at this level
+    // would overflow.
+    fn max_level() -> u32;
+    // None for the root
+    fn parent_key(&self) -> Option<Self>;
+    // The cell's x, y and z indices among the 2^level cells along each axis
+    fn coordinates(&self) -> (u64, u64, u64);
+    // Panics when the level is past max_level or an index doesn't fit in it
+    fn from_coordinates(x: u64, y: u64, z: u64, level: u32) -> Self;
+
+    // The key containing this one at a coarser level, or this key itself at
+    // its own level. None when the level is finer than the key.
+    fn ancestor_at(&self, level: u32) -> Option<Self> {
+        if level > self.level() {
+            return None;
+        }
+
+        let mut key = *self;
+        while key.level() > level {
+            key = key.parent_key()?;
+        }
+        Some(key)
+    }
+
+    // Strictly coarser and containing other
+    fn is_ancestor_of(&self, other: &Self) -> bool {
+        self.level() < other.level() && other.ancestor_at(self.level()) == Some(*self)
+    }
+
+    // The finest key containing both, which is one of them if it contains
+    // the other
+    fn common_ancestor(&self, other: &Self) -> Self {
+        let level = self.level().min(other.level());
+        let mut a = self.ancestor_at(level).unwrap();
+        let mut b = other.ancestor_at(level).unwrap();
+        while a != b {
+            a = a.parent_key().unwrap();
+            b = b.parent_key().unwrap();
+        }
+        a
+    }
+
+    // The leaf of an adaptive tree covering the same level neighbor, which
+    // is the neighbor itself or, where the tree stopped refining, one of its
+    // ancestors. None past the edge of the root or when no leaf covers it.
+    fn leaf_neighbor<P: Fn(&Self) -> bool>(&self, neighbor: Neighbor, is_leaf: P) -> Option<Self> {
+        let mut key = self.neighbor_key(neighbor)?;
+        loop {
+            if is_leaf(&key) {
+                return Some(key);
+            }
+            key = key.parent_key()?;
+        }
+    }
+}
+
+// Moves one component of a key at the given level, None if it leaves the
+// 2^level cells along that axis
+fn step_component(c: u64, relation: NeighborRelation, level: u32) -> Option<u64> {
+    match relation {
+        NeighborRelation::Less => c.checked_sub(1),
+        NeighborRelation::More if c + 1 < 1 << level => Some(c + 1),
+        NeighborRelation::More => None,
+        NeighborRelation::Same => Some(c),
+    }
+}
+
+// Out of range coordinates would spill into the bits of other axes or levels
+fn assert_coordinates<K: Key>(x: u64, y: u64, z: u64, level: u32) {
+    assert!(
+        level <= K::max_level(),
+        "Level {} is past the deepest, {}",
+        level,
+        K::max_level()
+    );
+    assert!(
+        (x | y | z) >> level == 0,
+        "Coordinates ({}, {}, {}) out of range at level {}",
+        x,
+        y,
+        z,
+        level
+    );
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Hash)]
@@ -126,6 +212,37 @@ impl Key for MortonKey {
         (((self.0 as f64).log2() / 3.0).floor()) as u32
     }
 
+    fn max_level() -> u32 {
+        COMPONENT_BIT_COUNT
+    }
+
+    fn coordinates(&self) -> (u64, u64, u64) {
+        let c = |i| self.get_component(i) as u64;
+        (c(0), c(1), c(2))
+    }
+
+    fn from_coordinates(x: u64, y: u64, z: u64, level: u32) -> MortonKey {
+        assert_coordinates::<MortonKey>(x, y, z, level);
+        MortonKey::from_components(x as u32, y as u32, z as u32, level)
+    }
+
+    fn parent_key(&self) -> Option<MortonKey> {
+        if self.0 == 1 {
+            None
+        } else {
+            Some(MortonKey(self.0 >> 3))
+        }
+    }
+
+    fn ancestor_at(&self, level: u32) -> Option<MortonKey> {
+        let own = self.level();
+        if level > own {
+            None
+        } else {
+            Some(MortonKey(self.0 >> (3 * (own - level))))
+        }
+    }
+
     fn neighbor_key(&self, neighbor: Neighbor) -> Option<MortonKey> {
         let level = self.level();
         let mut x = self.get_component(0);
@@ -263,6 +380,257 @@ impl MortonKey {
     }
 }
 
+// The same interleaving as MortonKey in a u128, for trees deeper than 21
+// levels. Components are dilated a bit at a time rather than with masks,
+// wide keys are for the rare very deep refinements.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Hash)]
+pub struct MortonKey128(pub u128);
+
+impl fmt::Debug for MortonKey128 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MortonKey128({:b})", self.0)
+    }
+}
+
+impl Key for MortonKey128 {
+    fn root_key() -> MortonKey128 {
+        MortonKey128(1)
+    }
+
+    fn child_key(&self, child: u64) -> MortonKey128 {
+        MortonKey128(self.0 << 3 | child as u128)
+    }
+
+    fn level(&self) -> u32 {
+        (127 - self.0.leading_zeros()) / 3
+    }
+
+    fn neighbor_key(&self, neighbor: Neighbor) -> Option<MortonKey128> {
+        let level = self.level();
+        let (x, y, z) = self.coordinates();
+        let x = step_component(x, neighbor.x, level)?;
+        let y = step_component(y, neighbor.y, level)?;
+        let z = step_component(z, neighbor.z, level)?;
+        Some(MortonKey128::from_coordinates(x, y, z, level))
+    }
+
+    fn max_level() -> u32 {
+        WIDE_COMPONENT_BIT_COUNT
+    }
+
+    fn coordinates(&self) -> (u64, u64, u64) {
+        let component = |shift| {
+            (0..self.level()).fold(0, |c, i| {
+                c | (((self.0 >> (3 * i + shift)) & 1) as u64) << i
+            })
+        };
+        (
+            component(ISOLATED_COMPONENT_SHIFTS[0]),
+            component(ISOLATED_COMPONENT_SHIFTS[1]),
+            component(ISOLATED_COMPONENT_SHIFTS[2]),
+        )
+    }
+
+    fn from_coordinates(x: u64, y: u64, z: u64, level: u32) -> MortonKey128 {
+        assert_coordinates::<MortonKey128>(x, y, z, level);
+        MortonKey128(
+            1 << (level * 3)
+                | MortonKey128::dilate_component(x) << 2
+                | MortonKey128::dilate_component(y) << 1
+                | MortonKey128::dilate_component(z),
+        )
+    }
+
+    fn parent_key(&self) -> Option<MortonKey128> {
+        if self.0 == 1 {
+            None
+        } else {
+            Some(MortonKey128(self.0 >> 3))
+        }
+    }
+
+    fn ancestor_at(&self, level: u32) -> Option<MortonKey128> {
+        let own = self.level();
+        if level > own {
+            None
+        } else {
+            Some(MortonKey128(self.0 >> (3 * (own - level))))
+        }
+    }
+}
+
+impl MortonKey128 {
+    pub fn dilate_component(c: u64) -> u128 {
+        (0..WIDE_COMPONENT_BIT_COUNT).fold(0, |d, i| d | (((c >> i) & 1) as u128) << (3 * i))
+    }
+}
+
+// Hilbert curve order, following Hamilton's "Compact Hilbert Indices". Each
+// level's digit is the child's octant, transformed by an entry corner and a
+// direction inherited from the digits above, then Gray decoded. Keys keep the
+// MortonKey layout, a leading one then three bits per level, and children are
+// still asked for by octant (x, y, z bits high to low), so keys nest the same
+// way and trees can switch between the two. Only the order they sort in
+// differs: consecutive keys at a level are always face neighbors.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Hash)]
+pub struct HilbertKey(pub u64);
+
+impl fmt::Debug for HilbertKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "HilbertKey({:b})", self.0)
+    }
+}
+
+fn gray(i: u64) -> u64 {
+    i ^ (i >> 1)
+}
+
+fn gray_inverse(g: u64) -> u64 {
+    (g ^ (g >> 1) ^ (g >> 2)) & 0b111
+}
+
+fn rotate_right(b: u64, r: u32) -> u64 {
+    let r = r % 3;
+    ((b >> r) | (b << (3 - r))) & 0b111
+}
+
+fn rotate_left(b: u64, r: u32) -> u64 {
+    rotate_right(b, 3 - r % 3)
+}
+
+// Corner the curve enters the w-th sub cube at
+fn entry(w: u64) -> u64 {
+    if w == 0 {
+        0
+    } else {
+        gray(2 * ((w - 1) / 2))
+    }
+}
+
+// Axis along which the curve leaves the w-th sub cube
+fn direction(w: u64) -> u32 {
+    if w == 0 {
+        0
+    } else if w % 2 == 0 {
+        (w - 1).trailing_ones() % 3
+    } else {
+        w.trailing_ones() % 3
+    }
+}
+
+// The entry corner and direction of the sub cube after the w-th
+#[derive(Copy, Clone, Debug)]
+struct HilbertState {
+    e: u64,
+    d: u32,
+}
+
+impl HilbertState {
+    fn root() -> HilbertState {
+        HilbertState { e: 0, d: 0 }
+    }
+
+    fn digit(&self, octant: u64) -> u64 {
+        gray_inverse(rotate_right(octant ^ self.e, self.d + 1))
+    }
+
+    fn octant(&self, digit: u64) -> u64 {
+        rotate_left(gray(digit), self.d + 1) ^ self.e
+    }
+
+    fn child(&self, digit: u64) -> HilbertState {
+        HilbertState {
+            e: self.e ^ rotate_left(entry(digit), self.d + 1),
+            d: (self.d + direction(digit) + 1) % 3,
+        }
+    }
+}
+
+impl Key for HilbertKey {
+    fn root_key() -> HilbertKey {
+        HilbertKey(1)
+    }
+
+    fn child_key(&self, octant: u64) -> HilbertKey {
+        HilbertKey(self.0 << 3 | self.state().digit(octant))
+    }
+
+    fn level(&self) -> u32 {
+        (63 - self.0.leading_zeros()) / 3
+    }
+
+    fn neighbor_key(&self, neighbor: Neighbor) -> Option<HilbertKey> {
+        let level = self.level();
+        let (x, y, z) = self.coordinates();
+        let x = step_component(x, neighbor.x, level)?;
+        let y = step_component(y, neighbor.y, level)?;
+        let z = step_component(z, neighbor.z, level)?;
+        Some(HilbertKey::from_coordinates(x, y, z, level))
+    }
+
+    fn max_level() -> u32 {
+        COMPONENT_BIT_COUNT
+    }
+
+    fn coordinates(&self) -> (u64, u64, u64) {
+        let mut state = HilbertState::root();
+        let mut c = (0, 0, 0);
+        for digit in self.digits() {
+            let octant = state.octant(digit);
+            c = (
+                c.0 << 1 | octant >> 2,
+                c.1 << 1 | (octant >> 1) & 1,
+                c.2 << 1 | octant & 1,
+            );
+            state = state.child(digit);
+        }
+        c
+    }
+
+    // Carries the state down rather than going through child_key, which
+    // replays it from the root at every level
+    fn from_coordinates(x: u64, y: u64, z: u64, level: u32) -> HilbertKey {
+        assert_coordinates::<HilbertKey>(x, y, z, level);
+        let mut state = HilbertState::root();
+        let mut key = HilbertKey::root_key().0;
+        for i in (0..level).rev() {
+            let octant = ((x >> i) & 1) << 2 | ((y >> i) & 1) << 1 | (z >> i) & 1;
+            let digit = state.digit(octant);
+            key = key << 3 | digit;
+            state = state.child(digit);
+        }
+        HilbertKey(key)
+    }
+
+    fn parent_key(&self) -> Option<HilbertKey> {
+        if self.0 == 1 {
+            None
+        } else {
+            Some(HilbertKey(self.0 >> 3))
+        }
+    }
+}
+
+impl HilbertKey {
+    fn digits(&self) -> impl Iterator<Item = u64> {
+        let (key, level) = (self.0, self.level());
+        (0..level).rev().map(move |i| (key >> (3 * i)) & 0b111)
+    }
+
+    fn state(&self) -> HilbertState {
+        self.digits()
+            .fold(HilbertState::root(), |state, digit| state.child(digit))
+    }
+
+    pub fn neighbors(self) -> impl Iterator<Item = HilbertKey> {
+        Neighbor::all_neighbors().filter_map(move |neighbor| self.neighbor_key(neighbor))
+    }
+
+    pub fn component_neighbors(self) -> impl Iterator<Item = HilbertKey> {
+        Neighbor::component_neighbors().filter_map(move |neighbor| self.neighbor_key(neighbor))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -778,4 +1146,350 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn wide_level() {
+        let mut k = MortonKey128::root_key();
+        assert_eq!(k.level(), 0);
+        for i in 0..WIDE_COMPONENT_BIT_COUNT {
+            k = k.child_key((i % 8) as u64);
+            assert_eq!(k.level(), i + 1);
+        }
+        assert_eq!(MortonKey128::max_level(), 42);
+        assert_eq!(MortonKey::max_level(), 21);
+    }
+
+    #[test]
+    fn wide_components() {
+        let mut k = MortonKey128::root_key();
+        for _ in 0..WIDE_COMPONENT_BIT_COUNT {
+            k = k.child_key(5);
+        }
+        let all = (1u64 << WIDE_COMPONENT_BIT_COUNT) - 1;
+        assert_eq!(k.coordinates(), (all, 0, all));
+
+        let (x, y, z) = (0x2a5_a5a5_a5a5, 0x123_4567_89ab, 0x3ff_0000_ffff);
+        let k = MortonKey128::from_coordinates(x, y, z, 42);
+        assert_eq!(k.level(), 42);
+        assert_eq!(k.coordinates(), (x, y, z));
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn coordinates_out_of_range() {
+        // 2^21 would spill into the level bit and the other axes
+        MortonKey::from_coordinates(1 << COMPONENT_BIT_COUNT, 0, 0, COMPONENT_BIT_COUNT);
+    }
+
+    #[test]
+    #[should_panic(expected = "past the deepest")]
+    fn level_out_of_range() {
+        MortonKey128::from_coordinates(0, 0, 0, WIDE_COMPONENT_BIT_COUNT + 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "past the deepest")]
+    fn hilbert_level_out_of_range() {
+        HilbertKey::from_coordinates(0, 0, 0, COMPONENT_BIT_COUNT + 1);
+    }
+
+    #[test]
+    fn wide_matches_narrow() {
+        let mut narrow = MortonKey::root_key();
+        let mut wide = MortonKey128::root_key();
+        for i in 0..6 {
+            narrow = narrow.child_key((i * 3 + 1) % 8);
+            wide = wide.child_key((i * 3 + 1) % 8);
+        }
+        assert_eq!(narrow.0 as u128, wide.0);
+
+        for neighbor in Neighbor::all_neighbors() {
+            assert_eq!(
+                narrow.neighbor_key(neighbor).map(|k| k.0 as u128),
+                wide.neighbor_key(neighbor).map(|k| k.0)
+            );
+        }
+    }
+
+    #[test]
+    fn wide_deep_neighbors() {
+        // A corner cell at the deepest level only has neighbors inwards
+        let mut k = MortonKey128::root_key();
+        for _ in 0..WIDE_COMPONENT_BIT_COUNT {
+            k = k.child_key(7);
+        }
+        let neighbors: Vec<MortonKey128> = Neighbor::all_neighbors()
+            .filter_map(|n| k.neighbor_key(n))
+            .collect();
+        assert_eq!(neighbors.len(), 7);
+
+        let less = k
+            .neighbor_key(Neighbor::from_components(
+                NeighborRelation::Less,
+                NeighborRelation::Same,
+                NeighborRelation::Same,
+            ))
+            .unwrap();
+        assert_eq!(less.level(), 42);
+        let (x, y, z) = k.coordinates();
+        assert_eq!(less.coordinates(), (x - 1, y, z));
+    }
+
+    #[test]
+    fn parents_and_ancestors() {
+        let root = MortonKey::root_key();
+        assert_eq!(root.parent_key(), None);
+
+        let mut path = vec![root];
+        for i in 0..10 {
+            let child = path[i].child_key((i * 5 % 8) as u64);
+            assert_eq!(child.parent_key(), Some(path[i]));
+            path.push(child);
+        }
+
+        let leaf = path[10];
+        for level in 0..11 {
+            assert_eq!(leaf.ancestor_at(level), Some(path[level as usize]));
+        }
+        assert_eq!(leaf.ancestor_at(11), None);
+
+        assert!(root.is_ancestor_of(&leaf));
+        assert!(path[4].is_ancestor_of(&leaf));
+        assert!(!leaf.is_ancestor_of(&leaf));
+        assert!(!leaf.is_ancestor_of(&path[4]));
+        assert!(!root.child_key(7).is_ancestor_of(&leaf));
+
+        // The shifting overrides agree with walking up parents
+        let wide = (0..10).fold(MortonKey128::root_key(), |k, i| k.child_key(i * 5 % 8));
+        assert_eq!(wide.ancestor_at(4).unwrap().0, path[4].0 as u128);
+        assert_eq!(wide.parent_key().unwrap().0, path[9].0 as u128);
+    }
+
+    #[test]
+    fn common_ancestors() {
+        let base = MortonKey::root_key().child_key(3).child_key(6);
+        let a = base.child_key(1).child_key(2);
+        let b = base.child_key(4);
+        assert_eq!(a.common_ancestor(&b), base);
+        assert_eq!(b.common_ancestor(&a), base);
+        assert_eq!(a.common_ancestor(&a), a);
+        assert_eq!(base.common_ancestor(&a), base);
+
+        let other = MortonKey::root_key().child_key(2);
+        assert_eq!(a.common_ancestor(&other), MortonKey::root_key());
+    }
+
+    #[test]
+    fn leaf_neighbors() {
+        use std::collections::HashSet;
+
+        // Lower x half refined two levels, upper x half left coarse
+        let root = MortonKey::root_key();
+        let mut leaves = HashSet::new();
+        for i in 0..8 {
+            let child = root.child_key(i);
+            if i & 4 == 0 {
+                for j in 0..8 {
+                    leaves.insert(child.child_key(j));
+                }
+            } else {
+                leaves.insert(child);
+            }
+        }
+
+        let more_x = Neighbor::from_components(
+            NeighborRelation::More,
+            NeighborRelation::Same,
+            NeighborRelation::Same,
+        );
+        let less_x = Neighbor::from_components(
+            NeighborRelation::Less,
+            NeighborRelation::Same,
+            NeighborRelation::Same,
+        );
+        let is_leaf = |k: &MortonKey| leaves.contains(k);
+
+        // Fine cell at x = 1 of 4, its neighbor at x = 2 is inside coarse cell 4
+        let fine = MortonKey::from_components(1, 2, 3, 2);
+        assert!(leaves.contains(&fine));
+        assert_eq!(
+            fine.leaf_neighbor(more_x, &is_leaf),
+            Some(MortonKey::from_components(1, 1, 1, 1))
+        );
+        // Same level neighbors that are leaves come back as they are
+        assert_eq!(
+            fine.leaf_neighbor(less_x, &is_leaf),
+            Some(MortonKey::from_components(0, 2, 3, 2))
+        );
+        // Past the edge of the root
+        let edge = MortonKey::from_components(0, 0, 0, 2);
+        assert_eq!(edge.leaf_neighbor(less_x, &is_leaf), None);
+    }
+
+    fn hilbert_path(octants: &[u64]) -> HilbertKey {
+        octants
+            .iter()
+            .fold(HilbertKey::root_key(), |key, &octant| key.child_key(octant))
+    }
+
+    #[test]
+    fn hilbert_round_trip() {
+        for level in 0..4 {
+            let n = 1 << level;
+            for (x, y, z) in (0..n)
+                .cartesian_product(0..n)
+                .cartesian_product(0..n)
+                .map(|((x, y), z)| (x, y, z))
+            {
+                let key = HilbertKey::from_coordinates(x, y, z, level);
+                assert_eq!(key.level(), level);
+                assert_eq!(key.coordinates(), (x, y, z));
+                if level > 0 {
+                    let parent = key.parent_key().unwrap();
+                    assert_eq!(parent.coordinates(), (x >> 1, y >> 1, z >> 1));
+                    let octant = (x & 1) << 2 | (y & 1) << 1 | z & 1;
+                    assert_eq!(parent.child_key(octant), key);
+                }
+            }
+        }
+
+        let key = hilbert_path(&[7, 0, 7, 0, 7, 0, 7, 0]);
+        assert_eq!(key.coordinates(), (0b10101010, 0b10101010, 0b10101010));
+        assert_eq!(key.ancestor_at(2), Some(hilbert_path(&[7, 0])));
+
+        // Deep keys agree with walking down child by child
+        let level = HilbertKey::max_level();
+        let (x, y, z) = (0x1F_2B3C, 0x0A_9E01, 0x15_5D77);
+        let octants: Vec<u64> = (0..level)
+            .rev()
+            .map(|i| ((x >> i) & 1) << 2 | ((y >> i) & 1) << 1 | (z >> i) & 1)
+            .collect();
+        let key = HilbertKey::from_coordinates(x, y, z, level);
+        assert_eq!(key, hilbert_path(&octants));
+        assert_eq!(key.coordinates(), (x, y, z));
+    }
+
+    #[test]
+    fn hilbert_curve_is_continuous() {
+        // Every level's cells in key order step to a face neighbor each time,
+        // covering the cube exactly once
+        for level in 1..5 {
+            let count = 1u64 << (3 * level);
+            let first = 1u64 << (3 * level);
+            let mut seen = ::std::collections::HashSet::new();
+            let mut previous: Option<(u64, u64, u64)> = None;
+            for i in 0..count {
+                let c = HilbertKey(first | i).coordinates();
+                assert!(seen.insert(c));
+                if let Some(p) = previous {
+                    let distance = (c.0 as i64 - p.0 as i64).abs()
+                        + (c.1 as i64 - p.1 as i64).abs()
+                        + (c.2 as i64 - p.2 as i64).abs();
+                    assert_eq!(distance, 1);
+                }
+                previous = Some(c);
+            }
+        }
+    }
+
+    #[test]
+    fn hilbert_root_neighbors() {
+        let root = HilbertKey::root_key();
+        for neighbor in Neighbor::all_neighbors() {
+            assert_eq!(root.neighbor_key(neighbor), None);
+        }
+        assert_eq!(
+            root.neighbor_key(Neighbor {
+                x: NeighborRelation::Same,
+                y: NeighborRelation::Same,
+                z: NeighborRelation::Same
+            }),
+            Some(root)
+        );
+    }
+
+    #[test]
+    fn hilbert_neighbors() {
+        let root = HilbertKey::root_key();
+
+        let mut child = root.child_key(0);
+        assert_eq!(
+            child.neighbor_key(Neighbor {
+                x: NeighborRelation::Same,
+                y: NeighborRelation::Same,
+                z: NeighborRelation::Same
+            }),
+            Some(child)
+        );
+        assert_eq!(
+            child.neighbor_key(Neighbor {
+                x: NeighborRelation::More,
+                y: NeighborRelation::More,
+                z: NeighborRelation::Same
+            }),
+            Some(root.child_key(0b110))
+        );
+        assert_eq!(
+            child.neighbor_key(Neighbor {
+                x: NeighborRelation::Less,
+                y: NeighborRelation::More,
+                z: NeighborRelation::Same
+            }),
+            None
+        );
+
+        child = root.child_key(7);
+        assert_eq!(
+            child.neighbor_key(Neighbor {
+                x: NeighborRelation::More,
+                y: NeighborRelation::More,
+                z: NeighborRelation::Same
+            }),
+            None
+        );
+        assert_eq!(
+            child.neighbor_key(Neighbor {
+                x: NeighborRelation::Same,
+                y: NeighborRelation::Same,
+                z: NeighborRelation::Less
+            }),
+            Some(root.child_key(0b110))
+        );
+
+        child = hilbert_path(&[7, 0, 7, 0, 7, 0, 7, 0]);
+        assert_eq!(
+            child.neighbor_key(Neighbor {
+                x: NeighborRelation::Less,
+                y: NeighborRelation::Less,
+                z: NeighborRelation::Less
+            }),
+            Some(hilbert_path(&[7, 0, 7, 0, 7, 0, 0, 7]))
+        );
+        assert_eq!(
+            child.neighbor_key(Neighbor {
+                x: NeighborRelation::More,
+                y: NeighborRelation::More,
+                z: NeighborRelation::More
+            }),
+            Some(hilbert_path(&[7, 0, 7, 0, 7, 0, 7, 7]))
+        );
+    }
+
+    #[test]
+    fn hilbert_matches_morton_neighbors() {
+        let (x, y, z) = (5, 0, 12);
+        let morton = MortonKey::from_components(x, y, z, 4);
+        let hilbert = HilbertKey::from_coordinates(x as u64, y as u64, z as u64, 4);
+        for neighbor in Neighbor::all_neighbors() {
+            let expected = morton.neighbor_key(neighbor).map(|k| {
+                let c = |i| k.get_component(i) as u64;
+                (c(0), c(1), c(2))
+            });
+            assert_eq!(
+                hilbert.neighbor_key(neighbor).map(|k| k.coordinates()),
+                expected
+            );
+        }
+        assert_eq!(hilbert.component_neighbors().count(), 5);
+    }
 }
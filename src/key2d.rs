@@ -0,0 +1,174 @@
+use key::NeighborRelation;
+use std::fmt;
+use std::hash::Hash;
+
+// Quadtree keys, laid out like key::MortonKey with two components instead of
+// three: a leading one bit, then two bits per level, x above y.
+
+pub const COMPONENT_BIT_COUNT_2D: u32 = 31;
+
+const COMPONENT_SHIFTS_2D: [u32; 2] = [1, 0];
+
+#[derive(Hash, Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq)]
+pub struct Neighbor2D {
+    pub x: NeighborRelation,
+    pub y: NeighborRelation,
+}
+
+const COMPONENT_NEIGHBORS_2D: [Neighbor2D; 4] = [
+    Neighbor2D {
+        x: NeighborRelation::Same,
+        y: NeighborRelation::Less,
+    },
+    Neighbor2D {
+        x: NeighborRelation::Same,
+        y: NeighborRelation::More,
+    },
+    Neighbor2D {
+        x: NeighborRelation::Less,
+        y: NeighborRelation::Same,
+    },
+    Neighbor2D {
+        x: NeighborRelation::More,
+        y: NeighborRelation::Same,
+    },
+];
+
+impl Neighbor2D {
+    pub fn from_components(x: NeighborRelation, y: NeighborRelation) -> Neighbor2D {
+        Neighbor2D { x, y }
+    }
+
+    pub fn component_neighbors() -> impl Iterator<Item = Neighbor2D> {
+        COMPONENT_NEIGHBORS_2D.iter().cloned()
+    }
+}
+
+pub trait Key2D: Hash + Sized + Copy + Clone + PartialEq + Eq + Ord {
+    fn root_key() -> Self;
+    fn child_key(&self, i: u64) -> Self;
+    fn level(&self) -> u32;
+    fn neighbor_key(&self, neighbor: Neighbor2D) -> Option<Self>;
+    fn max_level() -> u32;
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Hash)]
+pub struct MortonKey2D(pub u64);
+
+impl fmt::Debug for MortonKey2D {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MortonKey2D({:b})", self.0)
+    }
+}
+
+fn step_component(c: u32, relation: NeighborRelation, level: u32) -> Option<u32> {
+    match relation {
+        NeighborRelation::Less => c.checked_sub(1),
+        NeighborRelation::More if (c as u64) + 1 < 1 << level => Some(c + 1),
+        NeighborRelation::More => None,
+        NeighborRelation::Same => Some(c),
+    }
+}
+
+impl Key2D for MortonKey2D {
+    fn root_key() -> MortonKey2D {
+        MortonKey2D(1)
+    }
+
+    fn child_key(&self, child: u64) -> MortonKey2D {
+        MortonKey2D(self.0 << 2 | child)
+    }
+
+    fn level(&self) -> u32 {
+        (63 - self.0.leading_zeros()) / 2
+    }
+
+    fn neighbor_key(&self, neighbor: Neighbor2D) -> Option<MortonKey2D> {
+        let level = self.level();
+        let x = step_component(self.get_component(0), neighbor.x, level)?;
+        let y = step_component(self.get_component(1), neighbor.y, level)?;
+        Some(MortonKey2D::from_components(x, y, level))
+    }
+
+    fn max_level() -> u32 {
+        COMPONENT_BIT_COUNT_2D
+    }
+}
+
+impl MortonKey2D {
+    pub fn component_neighbors(self) -> impl Iterator<Item = MortonKey2D> {
+        Neighbor2D::component_neighbors().filter_map(move |neighbor| self.neighbor_key(neighbor))
+    }
+
+    pub fn get_component(&self, component: usize) -> u32 {
+        let shift = COMPONENT_SHIFTS_2D[component];
+        (0..self.level()).fold(0, |c, i| c | (((self.0 >> (2 * i + shift)) & 1) as u32) << i)
+    }
+
+    pub fn dilate_component(c: u32) -> u64 {
+        (0..COMPONENT_BIT_COUNT_2D).fold(0, |d, i| d | (((c >> i) & 1) as u64) << (2 * i))
+    }
+
+    pub fn from_components(x: u32, y: u32, level: u32) -> MortonKey2D {
+        MortonKey2D(
+            1 << (level * 2)
+                | MortonKey2D::dilate_component(x) << 1
+                | MortonKey2D::dilate_component(y),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level() {
+        let mut k = MortonKey2D::root_key();
+        assert_eq!(k.level(), 0);
+        for i in 0..COMPONENT_BIT_COUNT_2D {
+            k = k.child_key((i % 4) as u64);
+            assert_eq!(k.level(), i + 1);
+        }
+    }
+
+    #[test]
+    fn components() {
+        let mut k = MortonKey2D::root_key();
+        for i in 0..8 {
+            k = k.child_key(i % 4);
+        }
+        // Children 0, 1, 2, 3 repeating are x = 0011..., y = 0101...
+        assert_eq!(k.get_component(0), 0b00110011);
+        assert_eq!(k.get_component(1), 0b01010101);
+
+        let k = MortonKey2D::from_components(0b1011, 0b0110, 4);
+        assert_eq!(k.0, 0b1_10_01_11_10);
+        assert_eq!((k.get_component(0), k.get_component(1)), (0b1011, 0b0110));
+    }
+
+    #[test]
+    fn neighbors() {
+        let root = MortonKey2D::root_key();
+        assert_eq!(root.component_neighbors().count(), 0);
+
+        // Child 0 is the lower left quadrant, with neighbors up and right
+        let lower_left = root.child_key(0);
+        let mut neighbors: Vec<MortonKey2D> = lower_left.component_neighbors().collect();
+        neighbors.sort();
+        assert_eq!(neighbors, vec![root.child_key(1), root.child_key(2)]);
+
+        let k = MortonKey2D::from_components(5, 9, 4);
+        let up = k
+            .neighbor_key(Neighbor2D::from_components(
+                NeighborRelation::Same,
+                NeighborRelation::More,
+            ))
+            .unwrap();
+        assert_eq!((up.get_component(0), up.get_component(1)), (5, 10));
+        assert_eq!(k.component_neighbors().count(), 4);
+
+        let edge = MortonKey2D::from_components(15, 0, 4);
+        assert_eq!(edge.component_neighbors().count(), 2);
+    }
+}
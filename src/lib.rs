@@ -7,13 +7,31 @@ extern crate serde_json;
 
 #[macro_use]
 mod util;
+#[cfg(test)]
+mod test_util;
 
+pub mod animation;
+pub mod csg;
+pub mod curve_tree;
+pub mod deform;
 pub mod function;
 pub mod function_ir;
 pub mod gen;
+pub mod grid;
 pub mod interval;
+pub mod metaball;
+pub mod noise;
+pub mod offset;
 pub mod parser;
 pub mod parser_error;
+pub mod rbf;
+pub mod sdf;
+pub mod tpms;
+pub mod transform;
+pub mod triangle_mesh;
+pub mod triangle_mesh_error;
 //pub mod mtree;
 pub mod key;
+pub mod key2d;
 pub mod mesh_tree;
+pub mod mesh_tree_error;
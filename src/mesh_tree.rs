@@ -1,14 +1,14 @@
-use cgmath::Vector3;
+use cgmath::{InnerSpace, Vector3};
 use function::*;
 use geoprim::*;
-use interval::contains_zero;
-use interval::Interval;
+use interval::{contains_zero, is_bounded};
+use interval::{hull, merge_intervals, Interval};
 use itertools::Itertools;
 use key;
-use key::Key;
+use mesh_tree_error::{MeshTreeError, MeshTreeResult};
 use std::collections::{HashMap, HashSet};
+use std::io;
 use std::io::Write;
-use std::iter::FromIterator;
 use std::iter::Iterator;
 
 pub struct Geometry {
@@ -17,6 +17,36 @@ pub struct Geometry {
     pub lines: Vec<u32>,
 }
 
+impl Geometry {
+    // Wavefront OBJ, one-based indices
+    pub fn write_obj<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        for v in &self.vertices {
+            writeln!(writer, "v {} {} {}", v.x, v.y, v.z)?;
+        }
+        for t in self.triangles.chunks(3) {
+            writeln!(writer, "f {} {} {}", t[0] + 1, t[1] + 1, t[2] + 1)?;
+        }
+        for l in self.lines.chunks(2) {
+            writeln!(writer, "l {} {}", l[0] + 1, l[1] + 1)?;
+        }
+        Ok(())
+    }
+}
+
+// How many Newton sweeps to spend pulling a vertex towards the surface
+const NEWTON_VERTEX_ITERATIONS: usize = 4;
+
+#[derive(Debug, Copy, Clone)]
+pub enum NewtonResult {
+    // The cell provably contains no part of the surface
+    Excluded,
+    // Any part of the surface in the cell lies within this box
+    Contracted(BoundingBox),
+    // As above, and along at least one axis every line through the box
+    // crosses the surface exactly once, so the surface is definitely there
+    Certified(BoundingBox),
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct BoundingBox {
     pub x: Interval,
@@ -24,7 +54,43 @@ pub struct BoundingBox {
     pub z: Interval,
 }
 
+// The i-th of 2^level equal parts of an interval. Worked out in f64 from the
+// ends, so neighboring parts share their bound exactly and the outer parts
+// end exactly on the interval's bounds.
+fn part(interval: Interval, i: u64, level: u32) -> Interval {
+    let n = (1u64 << level) as f64;
+    let at = |i: u64| {
+        let t = i as f64 / n;
+        (interval.min as f64 * (1.0 - t) + interval.max as f64 * t) as f32
+    };
+    Interval::new(at(i), at(i + 1))
+}
+
+// Which of the 2^level parts of an interval v falls in, None outside it
+fn part_index(interval: Interval, v: f32, level: u32) -> Option<u64> {
+    if !(v >= interval.min && v <= interval.max) {
+        return None;
+    }
+
+    // The last part starting at or below v. Searched rather than worked out
+    // from v, since at deep levels many parts round to the same f32 bounds.
+    let (mut low, mut high) = (0, (1u64 << level) - 1);
+    while low < high {
+        let mid = low + (high - low + 1) / 2;
+        if part(interval, mid, level).min <= v {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    Some(low)
+}
+
 impl BoundingBox {
+    pub fn new(x: Interval, y: Interval, z: Interval) -> BoundingBox {
+        BoundingBox { x, y, z }
+    }
+
     pub fn split(&self) -> Vec<BoundingBox> {
         let x_is = self.x.split();
         let y_is = self.y.split();
@@ -41,13 +107,232 @@ impl BoundingBox {
             .collect()
     }
 
-    pub fn contains_root<F: Function>(&self, f: &Box<F>) -> bool {
+    pub fn bindings(&self) -> HashMap<char, Interval> {
         let mut bindings = HashMap::new();
         bindings.insert('x', self.x);
         bindings.insert('y', self.y);
         bindings.insert('z', self.z);
-        let intervals = f.evaluate_interval(&bindings);
-        contains_zero(&intervals)
+        bindings
+    }
+
+    // The cell a key addresses when this box is the root
+    pub fn cell<K: key::Key>(&self, key: &K) -> BoundingBox {
+        let (x, y, z) = key.coordinates();
+        let level = key.level();
+        BoundingBox {
+            x: part(self.x, x, level),
+            y: part(self.y, y, level),
+            z: part(self.z, z, level),
+        }
+    }
+
+    // The key of the cell at the given level containing p, None outside the
+    // box or past the deepest level keys can address. Points on a face shared
+    // by two cells go to the upper one.
+    pub fn key_at<K: key::Key>(&self, p: Vector3<f32>, level: u32) -> Option<K> {
+        if level > K::max_level() {
+            return None;
+        }
+
+        Some(K::from_coordinates(
+            part_index(self.x, p.x, level)?,
+            part_index(self.y, p.y, level)?,
+            part_index(self.z, p.z, level)?,
+            level,
+        ))
+    }
+
+    fn axis(&self, axis: usize) -> Interval {
+        match axis {
+            0 => self.x,
+            1 => self.y,
+            _ => self.z,
+        }
+    }
+
+    fn with_axis(&self, axis: usize, interval: Interval) -> BoundingBox {
+        let mut bb = self.clone();
+        match axis {
+            0 => bb.x = interval,
+            1 => bb.y = interval,
+            _ => bb.z = interval,
+        }
+        bb
+    }
+
+    // The natural and centered forms are both enclosures of f over the box,
+    // so a root needs zero in both. That is the same as checking their
+    // intersection, which is at least as tight as the better of the two.
+    pub fn contains_root<F: Function + ?Sized>(&self, f: &Box<F>) -> bool {
+        let intervals = f.evaluate_interval(&self.bindings());
+        contains_zero(&intervals) && contains_zero(&self.centered_form(f))
+    }
+
+    // An upper bound on |grad f| over the box, so |f(p) - f(q)| <= L |p - q|
+    // for p and q in it. A ray marching from p can step |f(p)| / L without
+    // crossing the surface, as long as the step stays inside the box. It's
+    // infinite when f gives no bound on its gradient, or an empty one.
+    pub fn lipschitz_bound<F: Function + ?Sized>(&self, f: &F) -> f32 {
+        let gradient = f.evaluate_gradient_interval(&self.bindings());
+        let squares = gradient
+            .iter()
+            .map(|g| hull(g).abs().square())
+            .fold(Interval::new(0.0, 0.0), |sum, s| hull(&sum.add(&s)));
+
+        if squares.is_empty() {
+            f32::INFINITY
+        } else {
+            squares.sqrt().max
+        }
+    }
+
+    // Mean value form f(c) + grad f(B) . (B - c). Its overestimate shrinks
+    // quadratically with the cell size, against linearly for evaluating f over
+    // the box directly, so it wins for cells that are small relative to the
+    // curvature. Where f is undefined at the center or its gradient is
+    // unbounded the form says nothing, so it's entire rather than empty.
+    pub fn centered_form<F: Function + ?Sized>(&self, f: &Box<F>) -> Vec<Interval> {
+        let center = self.center();
+        let point = BoundingBox {
+            x: Interval::new(center.x, center.x),
+            y: Interval::new(center.y, center.y),
+            z: Interval::new(center.z, center.z),
+        };
+        let gradient = f.evaluate_gradient_interval(&self.bindings());
+
+        let mut result = f.evaluate_interval(&point.bindings());
+        if !is_bounded(&result) || !gradient.iter().all(|g| is_bounded(g)) {
+            return vec![Interval::entire()];
+        }
+
+        for axis in 0..3 {
+            let offset = self.axis(axis).sub(&point.axis(axis));
+            let terms: Vec<Interval> = gradient[axis]
+                .iter()
+                .cartesian_product(&offset)
+                .flat_map(|(derivative, offset)| derivative.mul(offset))
+                .collect();
+
+            result = merge_intervals(
+                result
+                    .iter()
+                    .cartesian_product(&terms)
+                    .flat_map(|(value, term)| value.add(term))
+                    .collect(),
+            );
+        }
+
+        result
+    }
+
+    // One Gauss-Seidel sweep of the interval Newton operator, an axis at a
+    // time: N = c - f(c) / df(B), with the other two axes left as intervals.
+    // Roots can only be in N intersected with the box, and if N lands strictly
+    // inside the box there is exactly one root along every line in that axis.
+    pub fn newton<F: Function + ?Sized>(&self, f: &Box<F>) -> NewtonResult {
+        let mut bb = self.clone();
+        let mut certified = false;
+
+        for axis in 0..3 {
+            let current = bb.axis(axis);
+            let c = current.middle();
+            let derivatives = f.evaluate_gradient_interval(&bb.bindings())[axis].clone();
+            let values = f.evaluate_interval(&bb.with_axis(axis, Interval::new(c, c)).bindings());
+
+            // f undefined on the slice, or no derivative bound, tells us
+            // nothing about where the roots are
+            if hull(&values).is_empty() || hull(&derivatives).is_empty() {
+                continue;
+            }
+
+            let steps = merge_intervals(
+                values
+                    .iter()
+                    .cartesian_product(&derivatives)
+                    .flat_map(|(value, derivative)| {
+                        // f is flat along the axis and zero somewhere on the
+                        // slice, so the root could be anywhere
+                        if value.contains_zero() && derivative.contains_zero() {
+                            vec![Interval::entire()]
+                        } else {
+                            value.div(derivative)
+                        }
+                    })
+                    .collect(),
+            );
+            let newton = merge_intervals(
+                steps
+                    .iter()
+                    .flat_map(|step| Interval::new(c, c).sub(step))
+                    .collect(),
+            );
+
+            let pieces: Vec<Interval> = newton
+                .iter()
+                .map(|n| n.intersect(&current))
+                .filter(|n| !n.is_empty())
+                .collect();
+
+            if pieces.is_empty() {
+                return NewtonResult::Excluded;
+            }
+
+            if newton.len() == 1
+                && newton[0].min > current.min
+                && newton[0].max < current.max
+                && !contains_zero(&derivatives)
+            {
+                certified = true;
+            }
+
+            bb = bb.with_axis(axis, hull(&pieces));
+        }
+
+        if certified {
+            NewtonResult::Certified(bb)
+        } else {
+            NewtonResult::Contracted(bb)
+        }
+    }
+
+    // Runs a few Newton sweeps and returns the center of what is left, which
+    // sits closer to the surface than the center of the cell.
+    pub fn newton_vertex<F: Function + ?Sized>(&self, f: &Box<F>) -> Vector3<f32> {
+        let mut bb = self.clone();
+        for _ in 0..NEWTON_VERTEX_ITERATIONS {
+            match bb.newton(f) {
+                NewtonResult::Contracted(contracted) | NewtonResult::Certified(contracted) => {
+                    bb = contracted
+                }
+                NewtonResult::Excluded => break,
+            }
+        }
+        bb.center()
+    }
+
+    // The Newton vertex moved one gradient step towards the surface, kept
+    // inside the cell. The step lands where the tangent plane crosses zero,
+    // so it is only on the surface where the function is close to linear.
+    // With a single normal per cell, sharp edges and corners get rounded off.
+    pub fn surface_vertex<F: Function + ?Sized>(&self, f: &Box<F>) -> Vector3<f32> {
+        let v = self.newton_vertex(f);
+        let value = f.evaluate(v.x, v.y, v.z);
+        let g = f.gradient(v.x, v.y, v.z);
+        let length2 = g.magnitude2();
+        if !(length2 > 0.0) || !length2.is_finite() || !value.is_finite() {
+            return v;
+        }
+
+        let mut projected = v - g * (value / length2);
+        self.clamp_vector(&mut projected);
+        projected
+    }
+
+    fn is_excluded<F: Function + ?Sized>(&self, f: &Box<F>) -> bool {
+        match self.newton(f) {
+            NewtonResult::Excluded => true,
+            _ => false,
+        }
     }
 
     pub fn center(&self) -> Vector3<f32> {
@@ -95,74 +380,175 @@ impl BoundingBox {
     }
 }
 
-pub struct MeshTree<K: key::Key, F: Function> {
+pub struct MeshTree<K: key::Key, F: Function + ?Sized> {
     function: Box<F>,
     pub level: u32,
-    solution_map: HashMap<K, BoundingBox>,
+    // Cells are the root box subdivided, so only their keys are kept
+    bb: BoundingBox,
+    solution_set: HashSet<K>,
     vertex_map: HashMap<K, Vector3<f32>>,
+    normal_map: HashMap<K, Vector3<f32>>,
     edge_set: HashSet<(K, K)>,
     triangle_set: HashSet<[K; 3]>,
 }
 
-impl<F: Function> MeshTree<key::MortonKey, F> {
+impl<F: Function + ?Sized> MeshTree<key::MortonKey, F> {
     pub fn new(f: Box<F>, bb: BoundingBox) -> MeshTree<key::MortonKey, F> {
+        MeshTree::with_key(f, bb)
+    }
+}
+
+impl<K: key::Key, F: Function + ?Sized> MeshTree<K, F> {
+    // A tree keyed by K, e.g. `MeshTree::<MortonKey128, _>::with_key` for
+    // trees deeper than MortonKey can go
+    pub fn with_key(f: Box<F>, bb: BoundingBox) -> MeshTree<K, F> {
         let mut result = MeshTree {
             function: f,
             level: 0,
+            bb,
             edge_set: HashSet::new(),
-            solution_map: HashMap::new(),
+            solution_set: HashSet::new(),
             vertex_map: HashMap::new(),
+            normal_map: HashMap::new(),
             triangle_set: HashSet::new(),
         };
 
-        let root_key = key::MortonKey::root_key();
-        if bb.contains_root(&result.function) {
-            result.solution_map.insert(root_key, bb);
+        let root_key = K::root_key();
+        if bb.contains_root(&result.function) && !bb.is_excluded(&result.function) {
+            result.solution_set.insert(root_key);
         }
 
         result
     }
 
-    pub fn next_level(&mut self) {
-        self.vertex_map.clear();
-        self.edge_set.clear();
-        self.triangle_set.clear();
-        self.level += 1;
+    // A tree for a new function, starting from this one's cells rather than
+    // the root. Cells are only ever culled, never revived, so this is sound
+    // as long as the new surface lies inside the old cells, e.g. when the
+    // old function bounded a whole range of times and the new one is a
+    // single time in it.
+    pub fn rebind<G: Function + ?Sized>(&self, f: Box<G>) -> MeshTree<K, G> {
+        let solution_set = self
+            .solution_set
+            .iter()
+            .filter(|key| {
+                let bb = self.cell(key);
+                bb.contains_root(&f) && !bb.is_excluded(&f)
+            })
+            .cloned()
+            .collect();
+
+        MeshTree {
+            function: f,
+            level: self.level,
+            bb: self.bb,
+            edge_set: HashSet::new(),
+            solution_set,
+            vertex_map: HashMap::new(),
+            normal_map: HashMap::new(),
+            triangle_set: HashSet::new(),
+        }
+    }
 
-        let mut new_solution_map = HashMap::new();
+    // Fails, leaving the tree as it was, when the key type can't address
+    // another level or a kept cell is too narrow for f32 to split
+    pub fn next_level(&mut self) -> MeshTreeResult<()> {
+        if self.level >= K::max_level() {
+            return Err(MeshTreeError::MaxLevel {
+                max_level: K::max_level(),
+            });
+        }
 
-        for (key, bb) in &self.solution_map {
-            let child_keys: Vec<key::MortonKey> = (0..8u64).map(|i| key.child_key(i)).collect();
-            let child_bb = bb.split();
+        let mut new_solution_set = HashSet::new();
 
-            for i in 0..8 {
-                let child_key = child_keys[i];
-                let child_bb = child_bb[i];
-                if child_bb.contains_root(&self.function) {
-                    new_solution_map.insert(child_key, child_bb);
+        for key in &self.solution_set {
+            let bb = self.cell(key);
+            for i in 0..8u64 {
+                let child_key = key.child_key(i);
+                let child_bb = self.cell(&child_key);
+                if (0..3).any(|axis| {
+                    let (parent, child) = (bb.axis(axis), child_bb.axis(axis));
+                    parent.min < parent.max && child.min == child.max
+                }) {
+                    return Err(MeshTreeError::Resolution {
+                        level: self.level + 1,
+                    });
+                }
+                if child_bb.contains_root(&self.function) && !child_bb.is_excluded(&self.function) {
+                    new_solution_set.insert(child_key);
                 }
             }
         }
 
-        self.solution_map = new_solution_map;
+        self.vertex_map.clear();
+        self.normal_map.clear();
+        self.edge_set.clear();
+        self.triangle_set.clear();
+        self.level += 1;
+        self.solution_set = new_solution_set;
+        Ok(())
     }
 
     pub fn generate_vertex_map(&mut self) {
         self.vertex_map.clear();
-        for (key, bb) in &self.solution_map {
-            self.vertex_map.insert(key.clone(), bb.center());
+        for key in &self.solution_set {
+            let vertex = self.cell(key).surface_vertex(&self.function);
+            self.vertex_map.insert(key.clone(), vertex);
         }
+        self.generate_normal_map();
+    }
+
+    // Unit normals at each vertex, pointing towards positive values
+    fn generate_normal_map(&mut self) {
+        self.normal_map.clear();
+        for (key, v) in &self.vertex_map {
+            let g = self.function.gradient(v.x, v.y, v.z);
+            if g.magnitude2() > 0.0 {
+                self.normal_map.insert(key.clone(), g.normalize());
+            }
+        }
+    }
+
+    // The box of any key, whether or not the tree kept its cell
+    pub fn cell(&self, key: &K) -> BoundingBox {
+        self.bb.cell(key)
+    }
+
+    // The kept cell of the current level containing p, if any
+    pub fn key_at(&self, p: Vector3<f32>) -> Option<K> {
+        self.bb
+            .key_at(p, self.level)
+            .filter(|key| self.solution_set.contains(key))
+    }
+
+    pub fn vertex_normal(&self, key: &K) -> Option<Vector3<f32>> {
+        self.normal_map.get(key).cloned()
+    }
+
+    // Lipschitz bound of the function over a cell of the current level
+    pub fn lipschitz_bound(&self, key: &K) -> Option<f32> {
+        if self.solution_set.contains(key) {
+            Some(self.cell(key).lipschitz_bound(&*self.function))
+        } else {
+            None
+        }
+    }
+
+    pub fn lipschitz_bounds(&self) -> HashMap<K, f32> {
+        self.solution_set
+            .iter()
+            .map(|key| (key.clone(), self.cell(key).lipschitz_bound(&*self.function)))
+            .collect()
     }
 
     pub fn generate_edge_set(&mut self) {
-        let key_set: HashSet<key::MortonKey> =
-            HashSet::from_iter(self.solution_map.keys().map(|k| k.clone()));
-        for key in &key_set {
-            key.clone()
-                .component_neighbors()
+        let key_set = &self.solution_set;
+        let edge_set = &mut self.edge_set;
+        for key in key_set {
+            key::Neighbor::component_neighbors()
+                .filter_map(|neighbor| key.neighbor_key(neighbor))
                 .filter(|n_k| n_k > key && key_set.contains(n_k))
                 .for_each(|n_k| {
-                    self.edge_set.insert((key.clone(), n_k.clone()));
+                    edge_set.insert((key.clone(), n_k.clone()));
                 })
         }
     }
@@ -170,7 +556,9 @@ impl<F: Function> MeshTree<key::MortonKey, F> {
     pub fn relax_vertices(&mut self) {
         let mut new_vertex_map = HashMap::new();
         for (key, vertex) in &self.vertex_map {
-            let neighbors: Vec<key::MortonKey> = key.clone().component_neighbors().collect();
+            let neighbors: Vec<K> = key::Neighbor::component_neighbors()
+                .filter_map(|neighbor| key.neighbor_key(neighbor))
+                .collect();
 
             let mut sum = Vector3::new(0.0, 0.0, 0.0);
             let mut count = 0;
@@ -189,17 +577,17 @@ impl<F: Function> MeshTree<key::MortonKey, F> {
             sum /= count as f32;
 
             let mut new_v = vertex + 0.2 * (sum - vertex);
-            let bb = self.solution_map.get(&key).unwrap();
-            bb.clamp_vector(&mut new_v);
+            self.cell(key).clamp_vector(&mut new_v);
 
             new_vertex_map.insert(key.clone(), new_v);
         }
 
         self.vertex_map = new_vertex_map;
+        self.generate_normal_map();
     }
 
-    pub fn add_vertex_triangles(&mut self, vertex_key: key::MortonKey) {
-        let maybe_neighbors: Vec<Option<key::MortonKey>> = key::Neighbor::component_neighbors()
+    pub fn add_vertex_triangles(&mut self, vertex_key: K) {
+        let maybe_neighbors: Vec<Option<K>> = key::Neighbor::component_neighbors()
             .map(|neighbor| vertex_key.neighbor_key(neighbor))
             .map(|maybe_neighbor_key| {
                 if maybe_neighbor_key.is_none() {
@@ -208,7 +596,7 @@ impl<F: Function> MeshTree<key::MortonKey, F> {
 
                 let neighbor_key = maybe_neighbor_key.unwrap();
 
-                if self.solution_map.contains_key(&neighbor_key) {
+                if self.solution_set.contains(&neighbor_key) {
                     Some(neighbor_key)
                 } else {
                     None
@@ -248,12 +636,69 @@ impl<F: Function> MeshTree<key::MortonKey, F> {
     }
 
     pub fn generate_triangle_set(&mut self) {
-        let keys: Vec<key::MortonKey> = self.solution_map.keys().map(|k| k.clone()).collect();
+        let keys: Vec<K> = self.solution_set.iter().cloned().collect();
         for key in keys {
             self.add_vertex_triangles(key.clone());
         }
     }
 
+    // Indexed vertices and triangles from the vertex map and triangle set,
+    // with triangles wound counter clockwise seen from the positive side
+    pub fn geometry(&self) -> Geometry {
+        let mut keys: Vec<K> = self.vertex_map.keys().cloned().collect();
+        keys.sort();
+        let indices: HashMap<K, u32> = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| (key.clone(), i as u32))
+            .collect();
+
+        let mut triangle_keys: Vec<&[K; 3]> = self.triangle_set.iter().collect();
+        triangle_keys.sort();
+
+        let mut triangles = Vec::new();
+        for keys in triangle_keys {
+            let (a, b, c) = match (
+                indices.get(&keys[0]),
+                indices.get(&keys[1]),
+                indices.get(&keys[2]),
+            ) {
+                (Some(&a), Some(&b), Some(&c)) => (a, b, c),
+                _ => continue,
+            };
+
+            let (va, vb, vc) = (
+                self.vertex_map[&keys[0]],
+                self.vertex_map[&keys[1]],
+                self.vertex_map[&keys[2]],
+            );
+            let facing = (vb - va).cross(vc - va);
+            let centroid = (va + vb + vc) / 3.0;
+            if facing.dot(self.function.gradient(centroid.x, centroid.y, centroid.z)) < 0.0 {
+                triangles.extend_from_slice(&[a, c, b]);
+            } else {
+                triangles.extend_from_slice(&[a, b, c]);
+            }
+        }
+
+        let mut edges: Vec<&(K, K)> = self.edge_set.iter().collect();
+        edges.sort();
+
+        let mut lines = Vec::new();
+        for &&(ref k1, ref k2) in &edges {
+            if let (Some(&i1), Some(&i2)) = (indices.get(k1), indices.get(k2)) {
+                lines.push(i1);
+                lines.push(i2);
+            }
+        }
+
+        Geometry {
+            vertices: keys.iter().map(|key| self.vertex_map[key]).collect(),
+            triangles,
+            lines,
+        }
+    }
+
     pub fn add_to_plot(
         &self,
         add_bb: bool,
@@ -263,8 +708,8 @@ impl<F: Function> MeshTree<key::MortonKey, F> {
         plot: &mut Plot,
     ) {
         if add_bb {
-            for bb in self.solution_map.values() {
-                bb.add_to_plot(plot);
+            for key in &self.solution_set {
+                self.cell(key).add_to_plot(plot);
             }
         }
 
@@ -328,3 +773,427 @@ impl<F: Function> MeshTree<key::MortonKey, F> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use key::Key;
+    use parser::parse_expression;
+    use test_util::cell;
+
+    fn sphere() -> Box<::function_ir::Node> {
+        let input: Vec<char> = "x^2 + y^2 + z^2 - 1".chars().collect();
+        parse_expression(&input, 0).unwrap()
+    }
+
+    #[test]
+    fn centered_form_tighter_for_small_cells() {
+        let input: Vec<char> = "x*x - x + 1".chars().collect();
+        let f = parse_expression(&input, 0).unwrap();
+        let bb = cell((0.4, 0.6), (-1.0, 1.0), (-1.0, 1.0));
+
+        let natural = hull(&f.evaluate_interval(&bb.bindings()));
+        let centered = hull(&bb.centered_form(&f));
+        assert!(centered.width() < natural.width());
+        assert!(centered.min <= 0.75 && centered.max >= 0.75);
+
+        // Natural evaluation alone can't exclude this cell
+        let bb = cell((0.0, 1.0), (-1.0, 1.0), (-1.0, 1.0));
+        assert!(contains_zero(&f.evaluate_interval(&bb.bindings())));
+        assert!(!bb.contains_root(&f));
+
+        // Both forms keep a cell the surface passes through
+        let bb = cell((0.9, 1.1), (-0.1, 0.1), (-0.1, 0.1));
+        assert!(bb.contains_root(&sphere()));
+    }
+
+    #[test]
+    fn undefined_center_keeps_root() {
+        // 1/x is undefined at the center, but f(0.5, 2, 0) = 0 is in the cell
+        let input: Vec<char> = "1/x - y".chars().collect();
+        let f = parse_expression(&input, 0).unwrap();
+        let bb = cell((-1.0, 1.0), (-1.0, 3.0), (-1.0, 1.0));
+        assert!(bb.contains_root(&f));
+        assert!(!bb.is_excluded(&f));
+
+        let mut tree = MeshTree::new(f, bb);
+        for _ in 0..3 {
+            tree.next_level().unwrap();
+        }
+        tree.generate_vertex_map();
+        assert!(!tree.vertex_map.is_empty());
+    }
+
+    #[test]
+    fn newton_certifies_surface() {
+        let f = sphere();
+        let bb = cell((0.9, 1.1), (-0.05, 0.05), (-0.05, 0.05));
+        match bb.newton(&f) {
+            NewtonResult::Certified(contracted) => {
+                assert!(contracted.x.min >= 0.9 && contracted.x.max <= 1.1);
+                assert!(contracted.x.width() < bb.x.width());
+            }
+            result => panic!("Expected certified, found {:?}", result),
+        }
+    }
+
+    #[test]
+    fn newton_excludes_empty_cell() {
+        // x^2 - x + 1 is never zero, but natural interval evaluation over
+        // [0, 1] gives [0, 2]
+        let input: Vec<char> = "x*x - x + 1".chars().collect();
+        let f = parse_expression(&input, 0).unwrap();
+        let bb = cell((0.0, 1.0), (-1.0, 1.0), (-1.0, 1.0));
+        assert!(contains_zero(&f.evaluate_interval(&bb.bindings())));
+        match bb.newton(&f) {
+            NewtonResult::Excluded => (),
+            result => panic!("Expected excluded, found {:?}", result),
+        }
+    }
+
+    #[test]
+    fn newton_keeps_flat_cell() {
+        // The x derivative is exactly zero, which must not exclude the cell
+        let input: Vec<char> = "y".chars().collect();
+        let f = parse_expression(&input, 0).unwrap();
+        let bb = cell((0.0, 1.0), (-1.0, 1.0), (-1.0, 1.0));
+        match bb.newton(&f) {
+            NewtonResult::Excluded => panic!("Flat cell was excluded"),
+            _ => (),
+        }
+    }
+
+    #[test]
+    fn vertex_normals() {
+        let bb = cell((-1.5, 1.5), (-1.5, 1.5), (-1.5, 1.5));
+        let mut tree = MeshTree::new(sphere(), bb);
+        for _ in 0..3 {
+            tree.next_level().unwrap();
+        }
+        tree.generate_vertex_map();
+        assert!(!tree.vertex_map.is_empty());
+
+        for (key, v) in &tree.vertex_map {
+            // Vertices land on the sphere, with normals pointing outwards
+            assert!((v.magnitude() - 1.0).abs() < 0.05);
+            let n = tree.vertex_normal(key).unwrap();
+            assert!((n.magnitude() - 1.0).abs() < 1e-4);
+            assert!(n.dot(v.normalize()) > 0.99);
+        }
+    }
+
+    #[test]
+    fn geometry_faces_outwards() {
+        let mut tree = MeshTree::new(sphere(), cell((-1.5, 1.5), (-1.5, 1.5), (-1.5, 1.5)));
+        for _ in 0..3 {
+            tree.next_level().unwrap();
+        }
+        tree.generate_vertex_map();
+        tree.generate_triangle_set();
+
+        let geometry = tree.geometry();
+        assert_eq!(geometry.vertices.len(), tree.vertex_map.len());
+        assert_eq!(geometry.triangles.len(), 3 * tree.triangle_set.len());
+        for t in geometry.triangles.chunks(3) {
+            let (a, b, c) = (
+                geometry.vertices[t[0] as usize],
+                geometry.vertices[t[1] as usize],
+                geometry.vertices[t[2] as usize],
+            );
+            // Some triangles lie in planes through the center, edge on
+            assert!((b - a).cross(c - a).dot(a + b + c) > -1e-5);
+        }
+
+        let mut obj = Vec::new();
+        geometry.write_obj(&mut obj).unwrap();
+        let obj = String::from_utf8(obj).unwrap();
+        assert_eq!(
+            obj.lines().filter(|l| l.starts_with("v ")).count(),
+            geometry.vertices.len()
+        );
+        assert_eq!(
+            obj.lines().filter(|l| l.starts_with("f ")).count(),
+            tree.triangle_set.len()
+        );
+    }
+
+    #[test]
+    fn rebind_keeps_coarse_cells() {
+        let input: Vec<char> = "x^2 + y^2 + z^2 - 0.64".chars().collect();
+        let smaller = parse_expression(&input, 0).unwrap();
+        let bb = cell((-1.5, 1.5), (-1.5, 1.5), (-1.5, 1.5));
+
+        let mut tree = MeshTree::new(sphere(), bb);
+        tree.next_level().unwrap();
+        tree.next_level().unwrap();
+        let mut rebound = tree.rebind(smaller.clone());
+        assert_eq!(rebound.level, 2);
+        rebound.next_level().unwrap();
+
+        // The spheres are close enough that both pass through the same coarse
+        // cells, so refining from them matches meshing from scratch
+        let mut fresh = MeshTree::new(smaller, bb);
+        for _ in 0..3 {
+            fresh.next_level().unwrap();
+        }
+        let rebound_keys: HashSet<_> = rebound.solution_set.iter().collect();
+        let fresh_keys: HashSet<_> = fresh.solution_set.iter().collect();
+        assert_eq!(rebound_keys, fresh_keys);
+
+        // Cells the new function rules out are dropped
+        let input: Vec<char> = "x^2 + y^2 + z^2 - 0.01".chars().collect();
+        let tiny = tree.rebind(parse_expression(&input, 0).unwrap());
+        assert!(tiny.solution_set.len() < tree.solution_set.len());
+    }
+
+    #[test]
+    fn lipschitz_bounds_gradient() {
+        let f = sphere();
+        let bb = cell((0.0, 1.0), (0.0, 0.5), (-0.5, 0.0));
+        let l = bb.lipschitz_bound(&*f);
+        // |(2x, 2y, 2z)| is at most sqrt(4 + 1 + 1) over the cell
+        assert!(l >= 6.0f32.sqrt() && l < 6.0f32.sqrt() + 1e-3);
+
+        for i in 0..20 {
+            let t = i as f32 / 19.0;
+            let (p, q) = (
+                Vector3::new(t, 0.5 * (1.0 - t), -0.5 * t * t),
+                Vector3::new(1.0 - t, 0.5 * t * t, -0.5 * t),
+            );
+            let change = (Function::evaluate(&*f, p.x, p.y, p.z)
+                - Function::evaluate(&*f, q.x, q.y, q.z))
+                .abs();
+            assert!(change <= l * (p - q).magnitude() + 1e-5);
+        }
+
+        assert!(bb.lipschitz_bound(&ConstFunction { c: 1.0 }) < 1e-6);
+        let unbounded = ClosureFunction::new(
+            |x: f32, _: f32, _: f32| x,
+            |b: &HashMap<char, Interval>| vec![b[&'x']],
+        );
+        assert_eq!(bb.lipschitz_bound(&unbounded), ::std::f32::INFINITY);
+
+        // Undefined over the whole cell, so the gradient enclosure is empty
+        let input: Vec<char> = "(x - 5) ^ 0.5".chars().collect();
+        let undefined = parse_expression(&input, 0).unwrap();
+        assert!(hull(&undefined.evaluate_gradient_interval(&bb.bindings())[0]).is_empty());
+        assert_eq!(bb.lipschitz_bound(&*undefined), ::std::f32::INFINITY);
+    }
+
+    #[test]
+    fn tree_lipschitz_bounds() {
+        let mut tree = MeshTree::new(sphere(), cell((-1.5, 1.5), (-1.5, 1.5), (-1.5, 1.5)));
+        for _ in 0..3 {
+            tree.next_level().unwrap();
+        }
+
+        let bounds = tree.lipschitz_bounds();
+        assert_eq!(bounds.len(), tree.solution_set.len());
+        for key in &tree.solution_set {
+            let l = bounds[key];
+            assert_eq!(tree.lipschitz_bound(key), Some(l));
+            let c = tree.cell(key).center();
+            assert!(Function::gradient(&*tree.function, c.x, c.y, c.z).magnitude() <= l);
+            // |grad| = 2|p| never gets past the corners of the root box
+            assert!(l <= 2.0 * 1.5 * 3.0f32.sqrt() + 1e-4);
+        }
+        assert_eq!(tree.lipschitz_bound(&key::MortonKey::root_key()), None);
+    }
+
+    #[test]
+    fn next_level_stops_at_key_capacity() {
+        // Nothing to refine, so levels are cheap
+        let f = ConstFunction { c: 1.0 };
+        let mut tree = MeshTree::new(Box::new(f), cell((0.0, 1.0), (0.0, 1.0), (0.0, 1.0)));
+        for _ in 0..key::COMPONENT_BIT_COUNT {
+            tree.next_level().unwrap();
+        }
+        assert_eq!(
+            tree.next_level(),
+            Err(MeshTreeError::MaxLevel { max_level: 21 })
+        );
+        assert_eq!(tree.level, 21);
+
+        let mut wide: MeshTree<key::MortonKey128, _> =
+            MeshTree::with_key(Box::new(f), cell((0.0, 1.0), (0.0, 1.0), (0.0, 1.0)));
+        for _ in 0..key::WIDE_COMPONENT_BIT_COUNT {
+            wide.next_level().unwrap();
+        }
+        assert!(wide.next_level().is_err());
+    }
+
+    #[test]
+    fn wide_keys_mesh_deep_levels() {
+        // The plane only clips the corner, so deep levels stay small, and
+        // near the origin f32 still resolves the cells
+        let input: Vec<char> = "x + y + z - 0.000001".chars().collect();
+        let f = parse_expression(&input, 0).unwrap();
+        let mut tree: MeshTree<key::MortonKey128, _> =
+            MeshTree::with_key(f, cell((0.0, 1.0), (0.0, 1.0), (0.0, 1.0)));
+        for _ in 0..25 {
+            tree.next_level().unwrap();
+        }
+        tree.generate_vertex_map();
+        tree.generate_triangle_set();
+        assert!(!tree.triangle_set.is_empty());
+        for (key, v) in &tree.vertex_map {
+            let bb = tree.cell(key);
+            assert!(bb.x.max - bb.x.min > 0.0);
+            assert_eq!(tree.key_at(bb.center()), Some(*key));
+            assert!((v.x + v.y + v.z - 0.000001).abs() < 1e-7);
+        }
+
+        // Away from the origin the same cells can't be told apart
+        let input: Vec<char> = "x + y + z - 3.000001".chars().collect();
+        let f = parse_expression(&input, 0).unwrap();
+        let mut tree: MeshTree<key::MortonKey128, _> =
+            MeshTree::with_key(f, cell((1.0, 2.0), (1.0, 2.0), (1.0, 2.0)));
+        let error = (0..30).map(|_| tree.next_level()).find(|r| r.is_err());
+        let level = tree.level;
+        assert_eq!(
+            error,
+            Some(Err(MeshTreeError::Resolution { level: level + 1 }))
+        );
+        assert!(level > key::COMPONENT_BIT_COUNT && level < 30);
+    }
+
+    #[test]
+    fn wide_keys_mesh_the_same() {
+        let bb = cell((-1.5, 1.5), (-1.5, 1.5), (-1.5, 1.5));
+        let mut narrow = MeshTree::new(sphere(), bb);
+        let mut wide = MeshTree::<key::MortonKey128, _>::with_key(sphere(), bb);
+        for _ in 0..3 {
+            narrow.next_level().unwrap();
+            wide.next_level().unwrap();
+        }
+        narrow.generate_vertex_map();
+        narrow.generate_triangle_set();
+        wide.generate_vertex_map();
+        wide.generate_triangle_set();
+
+        // Both orderings follow the same interleaving, so even the indices match
+        let (a, b) = (narrow.geometry(), wide.geometry());
+        assert_eq!(a.vertices, b.vertices);
+        assert_eq!(a.triangles, b.triangles);
+    }
+
+    #[test]
+    fn hilbert_keys_mesh_the_same() {
+        let bb = cell((-1.5, 1.5), (-1.5, 1.5), (-1.5, 1.5));
+        let mut morton = MeshTree::new(sphere(), bb);
+        let mut hilbert = MeshTree::<key::HilbertKey, _>::with_key(sphere(), bb);
+        for _ in 0..3 {
+            morton.next_level().unwrap();
+            hilbert.next_level().unwrap();
+        }
+        morton.generate_vertex_map();
+        morton.generate_triangle_set();
+        hilbert.generate_vertex_map();
+        hilbert.generate_triangle_set();
+
+        // Same cells in a different order, so compare the vertices as sets
+        let (a, b) = (morton.geometry(), hilbert.geometry());
+        assert_eq!(a.triangles.len(), b.triangles.len());
+        let sorted = |mut v: Vec<Vector3<f32>>| {
+            v.sort_by(|p, q| (p.x, p.y, p.z).partial_cmp(&(q.x, q.y, q.z)).unwrap());
+            v
+        };
+        assert_eq!(sorted(a.vertices), sorted(b.vertices));
+    }
+
+    #[test]
+    fn cells_from_keys() {
+        let root = cell((-1.0, 2.0), (0.0, 1.0), (-0.5, 0.5));
+        let bb = root.cell(&key::MortonKey::root_key());
+        assert_eq!((bb.x.min, bb.x.max), (-1.0, 2.0));
+
+        // Children in key order are the split cells
+        let children = root.split();
+        for i in 0..8 {
+            let bb = root.cell(&key::HilbertKey::root_key().child_key(i));
+            assert_eq!(bb.center(), children[i as usize].center());
+        }
+
+        let k = key::MortonKey::from_components(3, 0, 7, 3);
+        let bb = root.cell(&k);
+        assert_similiar!(bb.x.min, -1.0 + 3.0 * 3.0 / 8.0);
+        assert_similiar!(bb.y.max, 1.0 / 8.0);
+        assert_eq!(bb.z.max, 0.5);
+
+        // Neighboring cells share their faces exactly, and every cell's
+        // center maps back to its key
+        for x in 0..8 {
+            for y in 0..8 {
+                for z in 0..8 {
+                    let k = key::HilbertKey::from_coordinates(x, y, z, 3);
+                    let bb = root.cell(&k);
+                    assert_eq!(root.key_at(bb.center(), 3), Some(k));
+                    if x < 7 {
+                        let upper = key::HilbertKey::from_coordinates(x + 1, y, z, 3);
+                        let next = root.cell(&upper);
+                        assert_eq!(bb.x.max, next.x.min);
+                        let face = Vector3::new(next.x.min, bb.y.min, bb.z.min);
+                        assert_eq!(root.key_at(face, 3), Some(upper));
+                    }
+                }
+            }
+        }
+
+        assert_eq!(
+            root.key_at(Vector3::new(2.0, 1.0, 0.5), 2),
+            Some(key::MortonKey::from_components(3, 3, 3, 2))
+        );
+        let outside: Option<key::MortonKey> = root.key_at(Vector3::new(2.1, 0.5, 0.0), 2);
+        assert_eq!(outside, None);
+
+        // Levels keys can't address have no key rather than a wrapped one
+        let center = Vector3::new(0.1, 0.2, 0.3);
+        let deepest: Option<key::MortonKey> = root.key_at(center, key::MortonKey::max_level());
+        assert!(deepest.is_some());
+        let deeper: Option<key::MortonKey> = root.key_at(center, key::MortonKey::max_level() + 1);
+        assert_eq!(deeper, None);
+        let wide: Option<key::MortonKey128> = root.key_at(center, 64);
+        assert_eq!(wide, None);
+    }
+
+    #[test]
+    fn tree_cells_and_keys() {
+        let mut tree = MeshTree::new(sphere(), cell((-1.5, 1.5), (-1.5, 1.5), (-1.5, 1.5)));
+        for _ in 0..3 {
+            tree.next_level().unwrap();
+        }
+
+        // Points on the surface are in kept cells, the center is not
+        let k = tree.key_at(Vector3::new(1.0, 0.0, 0.0)).unwrap();
+        assert_eq!(k.level(), 3);
+        let bb = tree.cell(&k);
+        assert!(bb.x.min <= 1.0 && bb.x.max >= 1.0);
+        assert_eq!(tree.key_at(Vector3::new(0.0, 0.0, 0.0)), None);
+        assert_eq!(tree.key_at(Vector3::new(2.0, 0.0, 0.0)), None);
+    }
+
+    #[test]
+    fn mesh_noisy_sphere() {
+        let input: Vec<char> = "x^2 + y^2 + z^2 - 1 + 0.2 * noise(3 * x, 3 * y, 3 * z)"
+            .chars()
+            .collect();
+        let f = parse_expression(&input, 0).unwrap();
+        let mut tree = MeshTree::new(f, cell((-1.5, 1.5), (-1.5, 1.5), (-1.5, 1.5)));
+        for _ in 0..4 {
+            tree.next_level().unwrap();
+        }
+        tree.generate_vertex_map();
+        tree.generate_triangle_set();
+        assert!(!tree.triangle_set.is_empty());
+    }
+
+    #[test]
+    fn newton_vertex_near_surface() {
+        let f = sphere();
+        let bb = cell((0.75, 1.25), (-0.25, 0.25), (-0.25, 0.25));
+        let v = bb.newton_vertex(&f);
+        let radius = (v.x * v.x + v.y * v.y + v.z * v.z).sqrt();
+        assert!((radius - 1.0).abs() < (bb.center().x - 1.0).abs() + 0.05);
+        assert!((v.x - 1.0).abs() < 0.1);
+    }
+}
@@ -0,0 +1,39 @@
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum MeshTreeError {
+    // Refining would need more levels than the key type can address
+    MaxLevel { max_level: u32 },
+    // Cells at this level would be narrower than f32 can tell apart
+    Resolution { level: u32 },
+}
+
+impl Error for MeshTreeError {
+    fn description(&self) -> &str {
+        "An error occured while refining the mesh tree"
+    }
+
+    fn cause(&self) -> Option<&dyn Error> {
+        None
+    }
+}
+
+impl fmt::Display for MeshTreeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &MeshTreeError::MaxLevel { max_level } => write!(
+                f,
+                "Key type only addresses {} levels, use a wider key",
+                max_level
+            ),
+            &MeshTreeError::Resolution { level } => write!(
+                f,
+                "Cells at level {} are narrower than f32 can represent",
+                level
+            ),
+        }
+    }
+}
+
+pub type MeshTreeResult<R> = Result<R, MeshTreeError>;
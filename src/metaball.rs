@@ -0,0 +1,290 @@
+use cgmath::{InnerSpace, Vector3};
+use function::Function;
+use interval::Interval;
+use std::collections::HashMap;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Ball {
+    pub center: Vector3<f32>,
+    // Nothing beyond the radius feels the ball
+    pub radius: f32,
+    // Negative weights carve blobs away
+    pub weight: f32,
+}
+
+impl Ball {
+    pub fn new(center: Vector3<f32>, radius: f32, weight: f32) -> Ball {
+        assert!(radius > 0.0, "Ball radius must be positive");
+        Ball {
+            center,
+            radius,
+            weight,
+        }
+    }
+
+    // Wyvill's kernel (1 - r^2/R^2)^3, in terms of the squared distance. One
+    // at the center, falling smoothly to zero at the radius.
+    fn kernel(&self, distance2: f32) -> f32 {
+        let s = 1.0 - distance2 / (self.radius * self.radius);
+        if s > 0.0 {
+            s * s * s
+        } else {
+            0.0
+        }
+    }
+}
+
+type Cell = (i32, i32, i32);
+
+// A blobby object, inside where the summed field of the balls passes the
+// threshold. Balls are bucketed in a hash grid with cells as big as the
+// largest ball, so a query only looks at balls that can reach it.
+#[derive(Clone, Debug)]
+pub struct Metaballs {
+    balls: Vec<Ball>,
+    threshold: f32,
+    cell_size: f32,
+    cells: HashMap<Cell, Vec<usize>>,
+}
+
+impl Metaballs {
+    pub fn new(balls: Vec<Ball>, threshold: f32) -> Metaballs {
+        let cell_size = balls.iter().map(|b| b.radius).fold(0.0, f32::max);
+        let mut metaballs = Metaballs {
+            balls,
+            threshold,
+            cell_size: if cell_size > 0.0 { cell_size } else { 1.0 },
+            cells: HashMap::new(),
+        };
+
+        for (i, ball) in metaballs.balls.iter().enumerate() {
+            let r = Vector3::new(ball.radius, ball.radius, ball.radius);
+            let (min, max) = (
+                metaballs.cell(ball.center - r),
+                metaballs.cell(ball.center + r),
+            );
+            for x in min.0..max.0 + 1 {
+                for y in min.1..max.1 + 1 {
+                    for z in min.2..max.2 + 1 {
+                        metaballs.cells.entry((x, y, z)).or_insert_with(Vec::new).push(i);
+                    }
+                }
+            }
+        }
+
+        metaballs
+    }
+
+    fn cell(&self, p: Vector3<f32>) -> Cell {
+        (
+            (p.x / self.cell_size).floor() as i32,
+            (p.y / self.cell_size).floor() as i32,
+            (p.z / self.cell_size).floor() as i32,
+        )
+    }
+
+    fn nearby(&self, p: Vector3<f32>) -> &[usize] {
+        self.cells
+            .get(&self.cell(p))
+            .map(|balls| &balls[..])
+            .unwrap_or(&[])
+    }
+
+    // Indices of balls whose support reaches the box. For boxes spanning
+    // more cells than there are balls it's cheaper to just try them all.
+    fn overlapping(&self, min: Vector3<f32>, max: Vector3<f32>) -> Vec<usize> {
+        let reaches = |i: &usize| {
+            let ball = &self.balls[*i];
+            (0..3).all(|axis| {
+                ball.center[axis] + ball.radius >= min[axis]
+                    && ball.center[axis] - ball.radius <= max[axis]
+            })
+        };
+
+        let (lo, hi) = (self.cell(min), self.cell(max));
+        let count = (hi.0 as i64 - lo.0 as i64 + 1)
+            * (hi.1 as i64 - lo.1 as i64 + 1)
+            * (hi.2 as i64 - lo.2 as i64 + 1);
+        if !(min.x.is_finite() && min.y.is_finite() && min.z.is_finite())
+            || !(max.x.is_finite() && max.y.is_finite() && max.z.is_finite())
+            || count > self.balls.len() as i64
+        {
+            return (0..self.balls.len()).filter(reaches).collect();
+        }
+
+        let mut indices = Vec::new();
+        for x in lo.0..hi.0 + 1 {
+            for y in lo.1..hi.1 + 1 {
+                for z in lo.2..hi.2 + 1 {
+                    if let Some(balls) = self.cells.get(&(x, y, z)) {
+                        indices.extend_from_slice(balls);
+                    }
+                }
+            }
+        }
+        indices.sort();
+        indices.dedup();
+        indices.retain(reaches);
+        indices
+    }
+}
+
+impl Function for Metaballs {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        let p = Vector3::new(x, y, z);
+        let field: f32 = self
+            .nearby(p)
+            .iter()
+            .map(|&i| {
+                let ball = &self.balls[i];
+                ball.weight * ball.kernel((p - ball.center).magnitude2())
+            })
+            .sum();
+        self.threshold - field
+    }
+
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        let b = [bindings[&'x'], bindings[&'y'], bindings[&'z']];
+        if b.iter().any(|i| i.is_empty()) {
+            return vec![Interval::empty()];
+        }
+
+        let min = Vector3::new(b[0].min, b[1].min, b[2].min);
+        let max = Vector3::new(b[0].max, b[1].max, b[2].max);
+
+        // The kernel only falls with distance, so each ball contributes
+        // between its values at the farthest and nearest points of the box
+        let mut field = (0.0, 0.0);
+        for i in self.overlapping(min, max) {
+            let ball = &self.balls[i];
+            let mut near = 0.0;
+            let mut far = 0.0;
+            for axis in 0..3 {
+                let c = ball.center[axis];
+                let (lo, hi) = (min[axis] - c, max[axis] - c);
+                let nearest = if lo > 0.0 {
+                    lo
+                } else if hi < 0.0 {
+                    hi
+                } else {
+                    0.0
+                };
+                let farthest = lo.abs().max(hi.abs());
+                near += nearest * nearest;
+                far += farthest * farthest;
+            }
+
+            let (low, high) = (
+                ball.weight * ball.kernel(far),
+                ball.weight * ball.kernel(near),
+            );
+            field.0 += low.min(high);
+            field.1 += low.max(high);
+        }
+
+        vec![Interval::widened(
+            self.threshold - field.1,
+            self.threshold - field.0,
+        )]
+    }
+
+    fn gradient(&self, x: f32, y: f32, z: f32) -> Vector3<f32> {
+        let p = Vector3::new(x, y, z);
+        self.nearby(p)
+            .iter()
+            .map(|&i| {
+                let ball = &self.balls[i];
+                let r2 = ball.radius * ball.radius;
+                let offset = p - ball.center;
+                let s = 1.0 - offset.magnitude2() / r2;
+                if s > 0.0 {
+                    offset * (6.0 * ball.weight * s * s / r2)
+                } else {
+                    Vector3::new(0.0, 0.0, 0.0)
+                }
+            })
+            .fold(Vector3::new(0.0, 0.0, 0.0), |sum, g| sum + g)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mesh_tree::MeshTree;
+    use test_util::{assert_bounds, cell};
+
+    // A row of overlapping balls, one of them carving
+    fn blobs() -> Metaballs {
+        let mut balls = Vec::new();
+        for i in 0..12 {
+            let t = i as f32;
+            balls.push(Ball::new(
+                Vector3::new(t * 0.4 - 2.0, (t * 1.3).sin() * 0.3, (t * 0.7).cos() * 0.2),
+                0.5 + 0.05 * (i % 3) as f32,
+                1.0,
+            ));
+        }
+        balls.push(Ball::new(Vector3::new(0.0, 0.3, 0.0), 0.4, -1.5));
+        Metaballs::new(balls, 0.3)
+    }
+
+    #[test]
+    fn single_ball() {
+        let m = Metaballs::new(vec![Ball::new(Vector3::new(1.0, 0.0, 0.0), 2.0, 1.0)], 0.125);
+        // (1 - r^2/4)^3 = 1/8 at r = sqrt(2)
+        assert!(m.evaluate(1.0 + 2.0f32.sqrt(), 0.0, 0.0).abs() < 1e-5);
+        assert_similiar!(m.evaluate(1.0, 0.0, 0.0), 0.125 - 1.0);
+        assert_similiar!(m.evaluate(5.0, 0.0, 0.0), 0.125);
+
+        let g = m.gradient(2.0, 0.0, 0.0);
+        let h = 1e-3;
+        let fd = (m.evaluate(2.0 + h, 0.0, 0.0) - m.evaluate(2.0 - h, 0.0, 0.0)) / (2.0 * h);
+        assert!((g.x - fd).abs() < 1e-2);
+    }
+
+    #[test]
+    fn hash_matches_brute_force() {
+        let m = blobs();
+        for i in 0..40 {
+            let t = i as f32;
+            let p = Vector3::new(t * 0.13 - 2.6, (t * 0.7).sin() * 0.6, (t * 0.3).cos() * 0.5);
+            let brute: f32 = m
+                .balls
+                .iter()
+                .map(|b| b.weight * b.kernel((p - b.center).magnitude2()))
+                .sum();
+            assert!((m.evaluate(p.x, p.y, p.z) - (m.threshold - brute)).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn interval_bounds() {
+        let m = blobs();
+        let boxes = [
+            cell((-3.0, 3.0), (-1.0, 1.0), (-1.0, 1.0)),
+            cell((-0.6, -0.2), (0.0, 0.4), (-0.1, 0.3)),
+            cell((0.9, 1.0), (-0.2, -0.1), (0.0, 0.05)),
+            cell((-100.0, 100.0), (-100.0, 100.0), (-100.0, 100.0)),
+        ];
+        for bb in &boxes {
+            assert_bounds(&m, bb);
+        }
+
+        // Out of reach of every ball the field is exactly the threshold
+        let far = m.evaluate_interval(&cell((5.0, 6.0), (5.0, 6.0), (5.0, 6.0)).bindings());
+        assert!(m.overlapping(Vector3::new(5.0, 5.0, 5.0), Vector3::new(6.0, 6.0, 6.0)).is_empty());
+        assert!(far[0].min > 0.29 && far[0].max < 0.31);
+    }
+
+    #[test]
+    fn mesh_blobs() {
+        let bb = cell((-3.0, 3.0), (-1.5, 1.5), (-1.5, 1.5));
+        let mut tree = MeshTree::new(Box::new(blobs()), bb);
+        for _ in 0..4 {
+            tree.next_level().unwrap();
+        }
+        tree.generate_vertex_map();
+        tree.generate_triangle_set();
+        assert_eq!(tree.level, 4);
+    }
+}
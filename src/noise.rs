@@ -0,0 +1,315 @@
+use cgmath::{InnerSpace, Vector3};
+use function::Function;
+use interval::Interval;
+use std::collections::HashMap;
+use std::fmt;
+
+// Ken Perlin's reference permutation, used by unseeded `noise(x, y, z)`
+pub const REFERENCE_PERMUTATION: [u8; 256] = [
+    151, 160, 137, 91, 90, 15, 131, 13, 201, 95, 96, 53, 194, 233, 7, 225, 140, 36, 103, 30, 69,
+    142, 8, 99, 37, 240, 21, 10, 23, 190, 6, 148, 247, 120, 234, 75, 0, 26, 197, 62, 94, 252, 219,
+    203, 117, 35, 11, 32, 57, 177, 33, 88, 237, 149, 56, 87, 174, 20, 125, 136, 171, 168, 68, 175,
+    74, 165, 71, 134, 139, 48, 27, 166, 77, 146, 158, 231, 83, 111, 229, 122, 60, 211, 133, 230,
+    220, 105, 92, 41, 55, 46, 245, 40, 244, 102, 143, 54, 65, 25, 63, 161, 1, 216, 80, 73, 209, 76,
+    132, 187, 208, 89, 18, 169, 200, 196, 135, 130, 116, 188, 159, 86, 164, 100, 109, 198, 173,
+    186, 3, 64, 52, 217, 226, 250, 124, 123, 5, 202, 38, 147, 118, 126, 255, 82, 85, 212, 207, 206,
+    59, 227, 47, 16, 58, 17, 182, 189, 28, 42, 223, 183, 170, 213, 119, 248, 152, 2, 44, 154, 163,
+    70, 221, 153, 101, 155, 167, 43, 172, 9, 129, 22, 39, 253, 19, 98, 108, 110, 79, 113, 224, 232,
+    178, 185, 112, 104, 218, 246, 97, 228, 251, 34, 242, 193, 238, 210, 144, 12, 191, 179, 162,
+    241, 81, 51, 145, 235, 249, 14, 239, 107, 49, 192, 214, 31, 181, 199, 106, 157, 184, 84, 204,
+    176, 115, 121, 50, 45, 127, 4, 150, 254, 138, 236, 205, 93, 222, 114, 67, 29, 24, 72, 243, 141,
+    128, 195, 78, 66, 215, 61, 156, 180,
+];
+
+// Noise is a blend of the eight corners' g.d, where the weights sum to one
+// and each gradient has two unit components, so |g.d| <= 2 bounds the value.
+const VALUE_BOUND: f32 = 2.0;
+
+// Along one axis the derivative is the blend of g_x (at most 1) plus the
+// weights' derivatives, which sum in magnitude to 2 max|fade'| = 3.75, times
+// |g.d| <= 2. So noise moves at most 8.5 per unit along each axis.
+const LIPSCHITZ: f32 = 8.5;
+
+// Step for differentiating past the analytic first derivative
+const DIFFERENCE_STEP: f32 = 1e-3;
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn fade_derivative(t: f32) -> f32 {
+    30.0 * t * t * (t * (t - 2.0) + 1.0)
+}
+
+// One of the twelve cube edge directions, chosen as in improved noise
+fn gradient_vector(hash: u8) -> Vector3<f32> {
+    let h = hash & 15;
+    let su = if h & 1 == 0 { 1.0 } else { -1.0 };
+    let sv = if h & 2 == 0 { 1.0 } else { -1.0 };
+
+    let mut g = Vector3::new(0.0, 0.0, 0.0);
+    if h < 8 {
+        g.x += su;
+    } else {
+        g.y += su;
+    }
+    if h < 4 {
+        g.y += sv;
+    } else if h == 12 || h == 14 {
+        g.x += sv;
+    } else {
+        g.z += sv;
+    }
+    g
+}
+
+// Perlin's improved noise and its gradient at p
+pub fn perlin(permutation: &[u8; 256], p: Vector3<f32>) -> (f32, Vector3<f32>) {
+    let floor = Vector3::new(p.x.floor(), p.y.floor(), p.z.floor());
+    let cell = [floor.x as i64, floor.y as i64, floor.z as i64];
+    let f = p - floor;
+    let w = [fade(f.x), fade(f.y), fade(f.z)];
+    let dw = [
+        fade_derivative(f.x),
+        fade_derivative(f.y),
+        fade_derivative(f.z),
+    ];
+
+    let hash = |i: i64, j: i64, k: i64| {
+        let a = permutation[(i & 255) as usize] as i64;
+        let b = permutation[((a + j) & 255) as usize] as i64;
+        permutation[((b + k) & 255) as usize]
+    };
+
+    let mut value = 0.0;
+    let mut gradient = Vector3::new(0.0, 0.0, 0.0);
+    for corner in 0..8 {
+        let c = [corner & 1, corner >> 1 & 1, corner >> 2 & 1];
+        let g = gradient_vector(hash(cell[0] + c[0], cell[1] + c[1], cell[2] + c[2]));
+        let a = g.dot(f - Vector3::new(c[0] as f32, c[1] as f32, c[2] as f32));
+
+        // Weight and its derivative along each axis for this corner
+        let mut weight = [0.0; 3];
+        let mut slope = [0.0; 3];
+        for axis in 0..3 {
+            if c[axis] == 0 {
+                weight[axis] = 1.0 - w[axis];
+                slope[axis] = -dw[axis];
+            } else {
+                weight[axis] = w[axis];
+                slope[axis] = dw[axis];
+            }
+        }
+
+        let blend = weight[0] * weight[1] * weight[2];
+        value += blend * a;
+        gradient += g * blend;
+        gradient += Vector3::new(
+            slope[0] * weight[1] * weight[2],
+            weight[0] * slope[1] * weight[2],
+            weight[0] * weight[1] * slope[2],
+        ) * a;
+    }
+
+    (value, gradient)
+}
+
+// A partial derivative of noise, `order` counting how many times along each
+// axis. Past the first derivative we difference the one below.
+pub fn noise_derivative(permutation: &[u8; 256], order: [u8; 3], p: Vector3<f32>) -> f32 {
+    let total: u8 = order.iter().sum();
+    if total == 0 {
+        return perlin(permutation, p).0;
+    }
+    if total == 1 {
+        let gradient = perlin(permutation, p).1;
+        return gradient[order.iter().position(|&o| o == 1).unwrap()];
+    }
+
+    let axis = order.iter().position(|&o| o > 0).unwrap();
+    let mut lower = order;
+    lower[axis] -= 1;
+    let mut step = Vector3::new(0.0, 0.0, 0.0);
+    step[axis] = DIFFERENCE_STEP;
+    (noise_derivative(permutation, lower, p + step)
+        - noise_derivative(permutation, lower, p - step))
+        / (2.0 * DIFFERENCE_STEP)
+}
+
+// Bounds on a partial derivative of noise over a box. The value is pinned
+// near its center by the Lipschitz constant, first derivatives are bounded
+// by it, and nothing is claimed about higher ones.
+pub fn noise_derivative_interval(
+    permutation: &[u8; 256],
+    order: [u8; 3],
+    x: Interval,
+    y: Interval,
+    z: Interval,
+) -> Interval {
+    if x.is_empty() || y.is_empty() || z.is_empty() {
+        return Interval::empty();
+    }
+
+    match order.iter().sum::<u8>() {
+        0 => {
+            let center = Vector3::new(x.middle(), y.middle(), z.middle());
+            let d = perlin(permutation, center).0;
+            let r = LIPSCHITZ * (x.width() + y.width() + z.width()) / 2.0;
+            Interval::widened(d - r, d + r).intersect(&Interval::new(-VALUE_BOUND, VALUE_BOUND))
+        }
+        1 => Interval::new(-LIPSCHITZ, LIPSCHITZ),
+        _ => Interval::entire(),
+    }
+}
+
+// Seedable gradient noise as a Function, varying over roughly unit
+// distances. Scale the input with a Transform for other frequencies.
+#[derive(Copy, Clone, PartialEq)]
+pub struct Perlin {
+    seed: Option<u64>,
+    permutation: [u8; 256],
+}
+
+impl Perlin {
+    // The same seed always gives the same noise
+    pub fn new(seed: u64) -> Perlin {
+        let mut permutation = [0u8; 256];
+        for (i, p) in permutation.iter_mut().enumerate() {
+            *p = i as u8;
+        }
+
+        // Fisher-Yates, driven by splitmix64
+        let mut state = seed;
+        let mut next = || {
+            state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            z ^ (z >> 31)
+        };
+        for i in (1..256).rev() {
+            let j = (next() % (i as u64 + 1)) as usize;
+            permutation.swap(i, j);
+        }
+
+        Perlin {
+            seed: Some(seed),
+            permutation,
+        }
+    }
+
+    pub fn permutation(&self) -> &[u8; 256] {
+        &self.permutation
+    }
+}
+
+// The seed stands in for the 256 entries of the permutation
+impl fmt::Debug for Perlin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Perlin").field("seed", &self.seed).finish()
+    }
+}
+
+impl Default for Perlin {
+    fn default() -> Perlin {
+        Perlin {
+            seed: None,
+            permutation: REFERENCE_PERMUTATION,
+        }
+    }
+}
+
+impl Function for Perlin {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        perlin(&self.permutation, Vector3::new(x, y, z)).0
+    }
+
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        vec![noise_derivative_interval(
+            &self.permutation,
+            [0, 0, 0],
+            bindings[&'x'],
+            bindings[&'y'],
+            bindings[&'z'],
+        )]
+    }
+
+    fn gradient(&self, x: f32, y: f32, z: f32) -> Vector3<f32> {
+        perlin(&self.permutation, Vector3::new(x, y, z)).1
+    }
+
+    fn evaluate_gradient_interval(
+        &self,
+        _bindings: &HashMap<char, Interval>,
+    ) -> [Vec<Interval>; 3] {
+        let bound = vec![Interval::new(-LIPSCHITZ, LIPSCHITZ)];
+        [bound.clone(), bound.clone(), bound]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use interval::hull;
+    use test_util::{assert_bounds_within, cell};
+
+    #[test]
+    fn reference_permutation() {
+        let mut sorted = REFERENCE_PERMUTATION.to_vec();
+        sorted.sort();
+        assert!(sorted.iter().enumerate().all(|(i, &p)| p as usize == i));
+
+        let mut sorted = Perlin::new(7).permutation.to_vec();
+        sorted.sort();
+        assert!(sorted.iter().enumerate().all(|(i, &p)| p as usize == i));
+    }
+
+    #[test]
+    fn deterministic() {
+        let (a, b, c) = (Perlin::new(1), Perlin::new(1), Perlin::new(2));
+        assert_eq!(a.evaluate(0.3, 1.7, -2.2), b.evaluate(0.3, 1.7, -2.2));
+        assert!(a.evaluate(0.3, 1.7, -2.2) != c.evaluate(0.3, 1.7, -2.2));
+
+        // Zero on the integer lattice, not elsewhere
+        let d = Perlin::default();
+        assert_eq!(d.evaluate(3.0, -4.0, 12.0), 0.0);
+        assert!(d.evaluate(3.5, -4.25, 12.75) != 0.0);
+    }
+
+    #[test]
+    fn gradient_matches_differences() {
+        let noise = Perlin::new(42);
+        let h = 1e-3;
+        for i in 0..20 {
+            let t = i as f32;
+            let p = Vector3::new(t * 0.37 - 3.0, (t * 0.9).sin() * 4.0, t * 0.11);
+            let g = noise.gradient(p.x, p.y, p.z);
+            let fd = Vector3::new(
+                noise.evaluate(p.x + h, p.y, p.z) - noise.evaluate(p.x - h, p.y, p.z),
+                noise.evaluate(p.x, p.y + h, p.z) - noise.evaluate(p.x, p.y - h, p.z),
+                noise.evaluate(p.x, p.y, p.z + h) - noise.evaluate(p.x, p.y, p.z - h),
+            ) / (2.0 * h);
+            assert!((g - fd).magnitude() < 2e-2);
+            assert!(g.x.abs() <= LIPSCHITZ && g.y.abs() <= LIPSCHITZ && g.z.abs() <= LIPSCHITZ);
+        }
+    }
+
+    #[test]
+    fn interval_bounds() {
+        let noise = Perlin::default();
+        let boxes = [
+            cell((0.1, 0.15), (0.2, 0.25), (-0.35, -0.3)),
+            cell((10.0, 10.5), (3.2, 3.6), (7.9, 8.1)),
+            cell((-1.0, 1.0), (-1.0, 1.0), (-1.0, 1.0)),
+        ];
+        for bb in &boxes {
+            let bound = hull(&noise.evaluate_interval(&bb.bindings()));
+            assert!(bound.min >= -VALUE_BOUND && bound.max <= VALUE_BOUND);
+            assert_bounds_within(&noise, bb, 0.0);
+        }
+
+        // Small cells get tight bounds
+        let small = hull(&noise.evaluate_interval(&boxes[0].bindings()));
+        assert!(small.width() < 2.0);
+    }
+}
@@ -0,0 +1,337 @@
+use cgmath::{InnerSpace, Vector3};
+use function::Function;
+use interval::{hull, is_bounded, merge_intervals, Interval};
+use std::collections::HashMap;
+use std::f32;
+
+// Offsets and shells are exact on distance fields. Other fields can be
+// divided by their gradient magnitude first, which is their distance to the
+// surface to first order, so a thickness comes out roughly the same all over.
+
+fn normalized_value<F: Function>(f: &F, x: f32, y: f32, z: f32) -> f32 {
+    let length = f.gradient(x, y, z).magnitude();
+    f.evaluate(x, y, z) / length
+}
+
+// f(B) / |grad f(B)|, which is unbounded when the gradient can vanish.
+// Without a gradient enclosure only the sign of f carries over, so a cell
+// is only culled on the side the offset moves away from.
+fn normalized_interval<F: Function>(f: &F, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+    let values = f.evaluate_interval(bindings);
+    let gradient = f.evaluate_gradient_interval(bindings);
+    if !gradient.iter().all(|g| is_bounded(g)) {
+        return merge_intervals(values.iter().map(sign).collect());
+    }
+
+    let squares = gradient
+        .iter()
+        .map(|g| hull(g).square())
+        .fold(Interval::new(0.0, 0.0), |sum, s| hull(&sum.add(&s)));
+    let length = squares.sqrt();
+
+    merge_intervals(values.iter().flat_map(|v| v.div(&length)).collect())
+}
+
+// Where an interval of values lies relative to zero, with unknown size
+fn sign(v: &Interval) -> Interval {
+    if v.is_empty() {
+        return *v;
+    }
+    let min = if v.min < 0.0 { f32::NEG_INFINITY } else { 0.0 };
+    let max = if v.max > 0.0 { f32::INFINITY } else { 0.0 };
+    Interval::new(min, max)
+}
+
+fn shift(intervals: &[Interval], d: f32) -> Vec<Interval> {
+    merge_intervals(
+        intervals
+            .iter()
+            .flat_map(|i| i.sub(&Interval::new(d, d)))
+            .collect(),
+    )
+}
+
+// f - distance, growing the solid by distance, or shrinking it when negative
+#[derive(Copy, Clone, Debug)]
+pub struct Offset<F> {
+    pub function: F,
+    pub distance: f32,
+    pub normalize: bool,
+}
+
+impl<F: Function> Offset<F> {
+    pub fn new(function: F, distance: f32) -> Offset<F> {
+        Offset {
+            function,
+            distance,
+            normalize: false,
+        }
+    }
+
+    // Offsets f / |grad f| instead. Cells only cull well when `function`
+    // bounds its gradient over boxes.
+    pub fn normalized(function: F, distance: f32) -> Offset<F> {
+        Offset {
+            function,
+            distance,
+            normalize: true,
+        }
+    }
+}
+
+impl<F: Function> Function for Offset<F> {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        if self.normalize {
+            normalized_value(&self.function, x, y, z) - self.distance
+        } else {
+            self.function.evaluate(x, y, z) - self.distance
+        }
+    }
+
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        if self.normalize {
+            shift(&normalized_interval(&self.function, bindings), self.distance)
+        } else {
+            shift(&self.function.evaluate_interval(bindings), self.distance)
+        }
+    }
+
+    // Normalized this is the unit normal, dropping the second derivative
+    // term of the true gradient, so it's only exact on the surface of f
+    fn gradient(&self, x: f32, y: f32, z: f32) -> Vector3<f32> {
+        if self.normalize {
+            let g = self.function.gradient(x, y, z);
+            g / g.magnitude()
+        } else {
+            self.function.gradient(x, y, z)
+        }
+    }
+
+    // The normalized field's gradient involves second derivatives, so it's
+    // left unbounded
+    fn evaluate_gradient_interval(
+        &self,
+        bindings: &HashMap<char, Interval>,
+    ) -> [Vec<Interval>; 3] {
+        if self.normalize {
+            let entire = vec![Interval::entire()];
+            [entire.clone(), entire.clone(), entire]
+        } else {
+            self.function.evaluate_gradient_interval(bindings)
+        }
+    }
+}
+
+// |f| - thickness / 2, a wall of the given thickness centered on the surface
+#[derive(Copy, Clone, Debug)]
+pub struct Shell<F> {
+    pub function: F,
+    pub thickness: f32,
+    pub normalize: bool,
+}
+
+impl<F: Function> Shell<F> {
+    pub fn new(function: F, thickness: f32) -> Shell<F> {
+        Shell {
+            function,
+            thickness,
+            normalize: false,
+        }
+    }
+
+    // A shell around f / |grad f|, culling well only when `function` bounds
+    // its gradient over boxes
+    pub fn normalized(function: F, thickness: f32) -> Shell<F> {
+        Shell {
+            function,
+            thickness,
+            normalize: true,
+        }
+    }
+}
+
+impl<F: Function> Function for Shell<F> {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        let value = if self.normalize {
+            normalized_value(&self.function, x, y, z)
+        } else {
+            self.function.evaluate(x, y, z)
+        };
+        value.abs() - self.thickness / 2.0
+    }
+
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        let values = if self.normalize {
+            normalized_interval(&self.function, bindings)
+        } else {
+            self.function.evaluate_interval(bindings)
+        };
+        let magnitudes: Vec<Interval> = values.iter().map(|v| v.abs()).collect();
+        shift(&merge_intervals(magnitudes), self.thickness / 2.0)
+    }
+
+    // Normalized, exact only on the surface of f, as for Offset
+    fn gradient(&self, x: f32, y: f32, z: f32) -> Vector3<f32> {
+        let g = self.function.gradient(x, y, z);
+        let g = if self.normalize { g / g.magnitude() } else { g };
+        if self.function.evaluate(x, y, z) < 0.0 {
+            -g
+        } else {
+            g
+        }
+    }
+
+    // Where f keeps one sign the gradient is grad f or its negation,
+    // otherwise it could be either
+    fn evaluate_gradient_interval(
+        &self,
+        bindings: &HashMap<char, Interval>,
+    ) -> [Vec<Interval>; 3] {
+        if self.normalize {
+            let entire = vec![Interval::entire()];
+            return [entire.clone(), entire.clone(), entire];
+        }
+
+        let value = hull(&self.function.evaluate_interval(bindings));
+        let gradient = self.function.evaluate_gradient_interval(bindings);
+        let signed = |g: &Vec<Interval>| {
+            let negated: Vec<Interval> = g.iter().map(|i| i.neg()).collect();
+            if value.min >= 0.0 {
+                g.clone()
+            } else if value.max <= 0.0 {
+                negated
+            } else {
+                vec![hull(&[hull(g), hull(&negated)])]
+            }
+        };
+        [signed(&gradient[0]), signed(&gradient[1]), signed(&gradient[2])]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use function::ClosureFunction;
+    use mesh_tree::MeshTree;
+    use parser::parse_expression;
+    use sdf::Sphere;
+    use test_util::{assert_bounds_within, cell};
+
+    // x^2 + y^2 + z^2 - 1, zero on the unit sphere but with gradient 2 there
+    fn squared() -> ::function_ir::Node {
+        let input: Vec<char> = "x^2 + y^2 + z^2 - 1".chars().collect();
+        *parse_expression(&input, 0).unwrap()
+    }
+
+    fn unit_sphere() -> Sphere {
+        Sphere::new(Vector3::new(0.0, 0.0, 0.0), 1.0)
+    }
+
+    #[test]
+    fn offset_distance_field() {
+        let grown = Offset::new(unit_sphere(), 0.5);
+        assert_similiar!(grown.evaluate(1.5, 0.0, 0.0), 0.0);
+        assert_similiar!(grown.evaluate(0.0, 0.0, 0.0), -1.5);
+        assert_similiar!(grown.gradient(0.0, 2.0, 0.0).y, 1.0);
+        assert_bounds_within(&grown, &cell((0.5, 1.5), (-0.2, 0.3), (0.0, 0.1)), 1e-4);
+    }
+
+    #[test]
+    fn shell_distance_field() {
+        let wall = Shell::new(unit_sphere(), 0.2);
+        assert_similiar!(wall.evaluate(1.0, 0.0, 0.0), -0.1);
+        assert_similiar!(wall.evaluate(1.1, 0.0, 0.0), 0.0);
+        assert_similiar!(wall.evaluate(0.9, 0.0, 0.0), 0.0);
+        assert_similiar!(wall.evaluate(0.0, 0.0, 0.0), 0.9);
+        // Inside the sphere the gradient points back towards the center
+        assert_similiar!(wall.gradient(0.5, 0.0, 0.0).x, -1.0);
+        assert_bounds_within(&wall, &cell((0.5, 1.5), (-0.2, 0.3), (0.0, 0.1)), 1e-4);
+
+        let inside = cell((0.2, 0.3), (0.0, 0.1), (0.0, 0.1));
+        let gradient = Shell::new(squared(), 0.2).evaluate_gradient_interval(&inside.bindings());
+        assert!(hull(&gradient[0]).max <= 0.0);
+    }
+
+    #[test]
+    fn normalized_thickness() {
+        // Unnormalized the offset lands at sqrt(1.5), normalized the field is
+        // close to distance, so it lands close to 1.5
+        let plain = Offset::new(squared(), 0.5);
+        assert_similiar!(plain.evaluate(1.5f32.sqrt(), 0.0, 0.0), 0.0);
+
+        let normalized = Offset::normalized(squared(), 0.5);
+        let v = normalized.evaluate(1.5, 0.0, 0.0);
+        assert!(v.abs() < 0.2);
+        assert_similiar!(normalized.evaluate(1.0, 0.0, 0.0), -0.5);
+        assert_bounds_within(&normalized, &cell((0.5, 1.5), (-0.2, 0.3), (0.0, 0.1)), 1e-4);
+
+        let shell = Shell::normalized(squared(), 0.2);
+        assert!(shell.evaluate(1.1, 0.0, 0.0).abs() < 0.01);
+        assert!(shell.evaluate(0.9, 0.0, 0.0).abs() < 0.02);
+        assert_bounds_within(&shell, &cell((0.5, 1.5), (-0.2, 0.3), (0.0, 0.1)), 1e-4);
+    }
+
+    #[test]
+    fn normalized_without_gradient_bounds() {
+        // x - 1 with no gradient enclosure, so only the sign of f is known
+        let f = ClosureFunction::new(
+            |x: f32, _y: f32, _z: f32| x - 1.0,
+            |bindings: &HashMap<char, Interval>| bindings[&'x'].sub(&Interval::new(1.0, 1.0)),
+        );
+        let grown = Offset::normalized(&f, 0.5);
+        let inside = cell((0.0, 0.5), (0.0, 1.0), (0.0, 1.0));
+        assert!(hull(&grown.evaluate_interval(&inside.bindings())).max <= -0.5);
+        let outside = cell((2.0, 3.0), (0.0, 1.0), (0.0, 1.0));
+        let outside = hull(&grown.evaluate_interval(&outside.bindings()));
+        assert!(outside.min <= 0.0 && outside.max >= 0.0);
+        assert_bounds_within(&grown, &cell((0.5, 3.0), (0.0, 1.0), (0.0, 1.0)), 1e-4);
+    }
+
+    #[test]
+    fn parse_offset_and_shell() {
+        let input: Vec<char> = "shell(x^2 + y^2 + z^2 - 1, 0.4)".chars().collect();
+        let parsed = parse_expression(&input, 0).unwrap();
+        let shell = Shell::new(squared(), 0.4);
+        for &(x, y, z) in [(0.1, 0.2, 0.3), (1.0, 0.5, -0.2), (0.0, 0.0, 1.0)].iter() {
+            assert_similiar!(Function::evaluate(&*parsed, x, y, z), shell.evaluate(x, y, z));
+        }
+
+        let input: Vec<char> = "offset(abs(x) - 1, 0.25)".chars().collect();
+        let parsed = parse_expression(&input, 0).unwrap();
+        assert_similiar!(Function::evaluate(&*parsed, -1.25, 0.0, 0.0), 0.0);
+        assert_similiar!(Function::gradient(&*parsed, -2.0, 0.0, 0.0).x, -1.0);
+
+        // The normalized forms match the normalized functions
+        let input: Vec<char> = "normalized_offset(x^2 + y^2 + z^2 - 1, 0.5)".chars().collect();
+        let parsed = parse_expression(&input, 0).unwrap();
+        let offset = Offset::normalized(squared(), 0.5);
+        let input: Vec<char> = "normalized_shell(x^2 + y^2 + z^2 - 1, 0.2)".chars().collect();
+        let parsed_shell = parse_expression(&input, 0).unwrap();
+        let shell = Shell::normalized(squared(), 0.2);
+        for &(x, y, z) in [(0.1, 0.2, 0.3), (1.0, 0.5, -0.2), (0.0, 0.0, 1.5)].iter() {
+            assert_similiar!(Function::evaluate(&*parsed, x, y, z), offset.evaluate(x, y, z));
+            assert_similiar!(
+                Function::evaluate(&*parsed_shell, x, y, z),
+                shell.evaluate(x, y, z)
+            );
+        }
+        assert_bounds_within(&*parsed, &cell((0.5, 1.5), (-0.2, 0.3), (0.0, 0.1)), 1e-4);
+    }
+
+    #[test]
+    fn mesh_shell() {
+        let f = Shell::new(unit_sphere(), 0.3);
+        let mut tree = MeshTree::new(Box::new(f), cell((-1.5, 1.5), (-1.5, 1.5), (-1.5, 1.5)));
+        for _ in 0..4 {
+            tree.next_level().unwrap();
+        }
+        tree.generate_vertex_map();
+        tree.generate_triangle_set();
+
+        // Both walls get meshed
+        let geometry = tree.geometry();
+        let radii: Vec<f32> = geometry.vertices.iter().map(|v| v.magnitude()).collect();
+        assert!(radii.iter().any(|&r| r < 1.0));
+        assert!(radii.iter().any(|&r| r > 1.0));
+    }
+}
@@ -1,5 +1,7 @@
 use function_ir::Node;
+use noise::Perlin;
 use parser_error::{Expected, ParseError, ParseResult};
+use tpms::{Surface, Variant};
 
 type BNode = Box<Node>;
 
@@ -137,11 +139,7 @@ fn parse_base<'a>(input: &'a [char], current_index: usize) -> ParseResult<(BNode
     }
 
     let (base, new_index) = match input[index] {
-        'x' | 'y' | 'z' => {
-            let node = Node::Variable(input[index]);
-            index = incr_index(&input, index);
-            (Box::new(node), index)
-        }
+        c if c.is_alphabetic() => parse_identifier(input, index)?,
         d if d.is_digit(10) => parse_number(input, index)?,
         c => {
             return Err(ParseError::UnexpectedChar {
@@ -163,6 +161,132 @@ fn parse_base<'a>(input: &'a [char], current_index: usize) -> ParseResult<(BNode
     Ok((result_node, new_index))
 }
 
+// Variables are single letters, anything followed by parentheses is a
+// function call
+fn parse_identifier<'a>(input: &'a [char], current_index: usize) -> ParseResult<(BNode, usize)> {
+    let mut end = current_index;
+    while end < input.len() && (input[end].is_alphanumeric() || input[end] == '_') {
+        end += 1;
+    }
+
+    let name: String = input[current_index..end].iter().collect();
+    let index = incr_index(&input, end - 1);
+    if index < input.len() && input[index] == '(' {
+        return parse_call(input, current_index, name, index);
+    }
+
+    match name.as_str() {
+        // t is time, bound per frame when animating
+        "x" | "y" | "z" | "t" => Ok((Box::new(Node::Variable(input[current_index])), index)),
+        _ => Err(ParseError::UnknownIdentifier {
+            pos: current_index,
+            name,
+        }),
+    }
+}
+
+fn parse_call<'a>(
+    input: &'a [char],
+    name_index: usize,
+    name: String,
+    open_index: usize,
+) -> ParseResult<(BNode, usize)> {
+    let mut arguments = Vec::new();
+    let mut index = try_incr_index(&input, open_index)?;
+
+    loop {
+        let (argument, new_index) = parse_add(&input, index)?;
+        arguments.push(argument);
+        check_index(&input, new_index)?;
+
+        match input[new_index] {
+            ',' => index = try_incr_index(&input, new_index)?,
+            ')' => {
+                index = incr_index(&input, new_index);
+                break;
+            }
+            c => {
+                return Err(ParseError::UnexpectedChar {
+                    pos: new_index,
+                    c: c,
+                    exp: Expected::Char(')'),
+                })
+            }
+        }
+    }
+
+    Ok((build_call(name, name_index, arguments)?, index))
+}
+
+// TPMS lattices are `gyroid(x, y, z, cell, thickness)` for sheets and
+// `gyroid_network(...)` for networks, likewise for the other surfaces
+fn lattice(name: &str) -> Option<(Surface, Variant)> {
+    if name.ends_with("_network") {
+        Surface::from_name(&name[..name.len() - "_network".len()]).map(|s| (s, Variant::Network))
+    } else {
+        Surface::from_name(name).map(|s| (s, Variant::Sheet))
+    }
+}
+
+fn build_call(name: String, pos: usize, mut arguments: Vec<BNode>) -> ParseResult<BNode> {
+    let expected = match name.as_str() {
+        "sin" | "cos" | "abs" => 1,
+        "offset" | "shell" | "normalized_offset" | "normalized_shell" => 2,
+        "noise" => 3,
+        n if lattice(n).is_some() => 5,
+        _ => return Err(ParseError::UnknownIdentifier { pos, name }),
+    };
+
+    // noise takes an optional fourth argument, the seed
+    let seeded = name == "noise" && arguments.len() == 4;
+    if arguments.len() != expected && !seeded {
+        return Err(ParseError::ArgumentCount {
+            pos,
+            name,
+            expected,
+            found: arguments.len(),
+        });
+    }
+
+    let perlin = if seeded {
+        match *arguments.pop().unwrap() {
+            Node::Constant(c) if c >= 0.0 && c.fract() == 0.0 => Perlin::new(c as u64),
+            _ => return Err(ParseError::InvalidSeed { pos }),
+        }
+    } else {
+        Perlin::default()
+    };
+
+    // The normalized forms divide the field by its gradient magnitude first
+    let field = |f: BNode| {
+        if name.starts_with("normalized_") {
+            Box::new(f.normalized())
+        } else {
+            f
+        }
+    };
+
+    let mut next = || arguments.remove(0);
+    let node = match name.as_str() {
+        "sin" => Node::Sin(next()),
+        "cos" => Node::Cos(next()),
+        "abs" => Node::Abs(next()),
+        "offset" | "normalized_offset" => Node::Sub(field(next()), next()),
+        // |f| - t / 2
+        "shell" | "normalized_shell" => Node::Sub(
+            Box::new(Node::Abs(field(next()))),
+            Box::new(Node::Div(next(), Box::new(Node::Constant(2.0)))),
+        ),
+        "noise" => Node::Noise([0, 0, 0], Box::new(perlin), next(), next(), next()),
+        n => {
+            let (surface, variant) = lattice(n).unwrap();
+            surface.lattice_node(variant, *next(), *next(), *next(), *next(), *next())
+        }
+    };
+
+    Ok(Box::new(node))
+}
+
 fn parse_number<'a>(input: &'a [char], current_index: usize) -> ParseResult<(BNode, usize)> {
     let mut index = current_index;
     if !input[index].is_digit(10) {
@@ -414,6 +538,80 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_call() {
+        let mut input: Vec<char>;
+        let mut result;
+
+        input = "noise(x, 2 * y, z)".chars().collect();
+        result = parse_primary(&input, 0).unwrap();
+        assert_eq!(
+            format!("{:?}", result.0),
+            "Noise([0, 0, 0], Perlin { seed: None }, Variable(\'x\'), Mul(Constant(2.0), Variable(\'y\')), Variable(\'z\'))"
+        );
+        assert_eq!(result.1, 18);
+
+        input = "1 + -noise (x,y,z) * 2".chars().collect();
+        let root = parse_expression(&input, 0).unwrap();
+        assert_eq!(
+            format!("{:?}", root),
+            "Add(Constant(1.0), Mul(Mul(Constant(-1.0), Noise([0, 0, 0], Perlin { seed: None }, Variable(\'x\'), Variable(\'y\'), Variable(\'z\'))), Constant(2.0)))"
+        );
+
+        input = "noise(x, y)".chars().collect();
+        let err = parse_expression(&input, 0).unwrap_err();
+        assert_eq!(format!("{}", err), "noise takes 3 arguments, found 2 at 0");
+
+        input = "noise(x, y, z, 7)".chars().collect();
+        let root = parse_expression(&input, 0).unwrap();
+        assert_eq!(
+            format!("{:?}", root),
+            "Noise([0, 0, 0], Perlin { seed: Some(7) }, Variable(\'x\'), Variable(\'y\'), Variable(\'z\'))"
+        );
+
+        input = "noise(x, y, z, 1.5)".chars().collect();
+        let err = parse_expression(&input, 0).unwrap_err();
+        assert_eq!(format!("{}", err), "Invalid noise seed at 0");
+
+        input = "noise(x, y, z, x)".chars().collect();
+        let err = parse_expression(&input, 0).unwrap_err();
+        assert_eq!(err, ParseError::InvalidSeed { pos: 0 });
+
+        input = "2 * foo(x)".chars().collect();
+        let err = parse_expression(&input, 0).unwrap_err();
+        assert_eq!(format!("{}", err), "Unknown identifier foo at 4");
+
+        input = "sin(x) * cos(2 * y)".chars().collect();
+        let root = parse_expression(&input, 0).unwrap();
+        assert_eq!(
+            format!("{:?}", root),
+            "Mul(Sin(Variable(\'x\')), Cos(Mul(Constant(2.0), Variable(\'y\'))))"
+        );
+
+        input = "gyroid_network(x, y, z, 1)".chars().collect();
+        let err = parse_expression(&input, 0).unwrap_err();
+        assert_eq!(format!("{}", err), "gyroid_network takes 5 arguments, found 4 at 0");
+
+        input = "x - t".chars().collect();
+        let root = parse_expression(&input, 0).unwrap();
+        assert_eq!(
+            format!("{:?}", root),
+            "Sub(Variable(\'x\'), Variable(\'t\'))"
+        );
+
+        input = "x + w".chars().collect();
+        let err = parse_expression(&input, 0).unwrap_err();
+        assert_eq!(format!("{}", err), "Unknown identifier w at 4");
+
+        input = "noise(x, y, z".chars().collect();
+        let err = parse_expression(&input, 0).unwrap_err();
+        assert_eq!(format!("{}", err), "Unexpected end of input");
+
+        input = "noise(x; y, z)".chars().collect();
+        let err = parse_expression(&input, 0).unwrap_err();
+        assert_eq!(format!("{}", err), "Looking for character: ), found ; at 7");
+    }
+
     // TODO add more parse_expression tests now that the old ones moved to
     // function_ir
 }
@@ -25,6 +25,14 @@ pub enum ParseError {
     Float(String),
     UnexpectedEnd,
     UnconsumedInput(usize),
+    UnknownIdentifier { pos: usize, name: String },
+    ArgumentCount {
+        pos: usize,
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    InvalidSeed { pos: usize },
 }
 
 impl Error for ParseError {
@@ -48,6 +56,20 @@ impl fmt::Display for ParseError {
             &ParseError::Float(ref s) => f.write_fmt(format_args!("{}", s)),
             &ParseError::UnexpectedEnd => write!(f, "Unexpected end of input"),
             &ParseError::UnconsumedInput(p) => write!(f, "Unconsumed input starting at {}", p),
+            &ParseError::UnknownIdentifier { pos, ref name } => {
+                write!(f, "Unknown identifier {} at {}", name, pos)
+            }
+            &ParseError::ArgumentCount {
+                pos,
+                ref name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} takes {} arguments, found {} at {}",
+                name, expected, found, pos
+            ),
+            &ParseError::InvalidSeed { pos } => write!(f, "Invalid noise seed at {}", pos),
         }
     }
 }
@@ -0,0 +1,373 @@
+use cgmath::Vector3;
+use function::Function;
+use interval::{hull, Interval};
+use std::collections::HashMap;
+
+// Pivots this much smaller than the largest entry mean the system is singular
+const SINGULAR_TOLERANCE: f64 = 1e-12;
+
+// Solves a x = b in place by Gaussian elimination with partial pivoting, a
+// being n by n and row major
+fn solve(mut a: Vec<f64>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+    let scale = a.iter().fold(0.0f64, |m, v| m.max(v.abs()));
+
+    for column in 0..n {
+        let pivot = (column..n)
+            .max_by(|&i, &j| {
+                a[i * n + column]
+                    .abs()
+                    .partial_cmp(&a[j * n + column].abs())
+                    .unwrap()
+            })
+            .unwrap();
+        if !(a[pivot * n + column].abs() > SINGULAR_TOLERANCE * scale) {
+            return None;
+        }
+
+        if pivot != column {
+            for k in 0..n {
+                a.swap(pivot * n + k, column * n + k);
+            }
+            b.swap(pivot, column);
+        }
+
+        for row in column + 1..n {
+            let factor = a[row * n + column] / a[column * n + column];
+            if factor == 0.0 {
+                continue;
+            }
+            for k in column..n {
+                a[row * n + k] -= factor * a[column * n + k];
+            }
+            b[row] -= factor * b[column];
+        }
+    }
+
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let mut sum = b[row];
+        for k in row + 1..n {
+            sum -= a[row * n + k] * x[k];
+        }
+        x[row] = sum / a[row * n + row];
+    }
+    Some(x)
+}
+
+// A smooth field from scattered values, sum w_i |p - c_i|^3 plus a linear
+// polynomial. The cubic kernel needs no shape parameter and the polynomial
+// makes the fit exact for linear data.
+//
+// The fit is a dense solve, O(n^2) memory and O(n^3) time in the number of
+// centers, and every evaluation sums over all of them. That keeps it to a few
+// thousand centers, which is a couple of thousand points for `fit`; scanned
+// point clouds need decimating first.
+#[derive(Clone, Debug)]
+pub struct Rbf {
+    centers: Vec<Vector3<f64>>,
+    weights: Vec<f64>,
+    // Constant, then the x, y and z coefficients
+    polynomial: [f64; 4],
+}
+
+impl Rbf {
+    // Interpolates `values` at `centers`. None if the system is singular,
+    // such as when centers repeat or they all lie in one plane.
+    pub fn interpolate(centers: &[Vector3<f32>], values: &[f32]) -> Option<Rbf> {
+        assert_eq!(centers.len(), values.len());
+        let centers: Vec<Vector3<f64>> = centers
+            .iter()
+            .map(|c| Vector3::new(c.x as f64, c.y as f64, c.z as f64))
+            .collect();
+
+        // [A P; P^T 0] [w; a] = [values; 0]
+        let n = centers.len() + 4;
+        let mut a = vec![0.0; n * n];
+        for (i, ci) in centers.iter().enumerate() {
+            for (j, cj) in centers.iter().enumerate() {
+                a[i * n + j] = kernel(distance(*ci, *cj));
+            }
+            let row = [1.0, ci.x, ci.y, ci.z];
+            for k in 0..4 {
+                let j = centers.len() + k;
+                a[i * n + j] = row[k];
+                a[j * n + i] = row[k];
+            }
+        }
+
+        let mut b: Vec<f64> = values.iter().map(|&v| v as f64).collect();
+        b.extend_from_slice(&[0.0; 4]);
+
+        let x = solve(a, b)?;
+        let weights = x[..centers.len()].to_vec();
+        let p = &x[centers.len()..];
+        Some(Rbf {
+            centers,
+            weights,
+            polynomial: [p[0], p[1], p[2], p[3]],
+        })
+    }
+
+    // A surface through `points`, negative inside. Each point is also pushed
+    // `offset` out along its normal and given the value `offset`, which stops
+    // the fit from collapsing to zero everywhere and fixes the orientation.
+    // That doubles the centers, so mind the size limit above.
+    pub fn fit(points: &[Vector3<f32>], normals: &[Vector3<f32>], offset: f32) -> Option<Rbf> {
+        assert_eq!(points.len(), normals.len());
+        let mut centers = points.to_vec();
+        let mut values = vec![0.0; points.len()];
+        for (p, n) in points.iter().zip(normals) {
+            centers.push(p + n * offset);
+            values.push(offset);
+        }
+
+        Rbf::interpolate(&centers, &values)
+    }
+
+    fn evaluate_f64(&self, p: Vector3<f64>) -> f64 {
+        let a = &self.polynomial;
+        let mut sum = a[0] + a[1] * p.x + a[2] * p.y + a[3] * p.z;
+        for (c, w) in self.centers.iter().zip(&self.weights) {
+            sum += w * kernel(distance(p, *c));
+        }
+        sum
+    }
+}
+
+fn kernel(r: f64) -> f64 {
+    r * r * r
+}
+
+fn distance(a: Vector3<f64>, b: Vector3<f64>) -> f64 {
+    let d = a - b;
+    (d.x * d.x + d.y * d.y + d.z * d.z).sqrt()
+}
+
+// Nearest and farthest distances from c to points of the box
+fn distance_range(min: Vector3<f64>, max: Vector3<f64>, c: Vector3<f64>) -> (f64, f64) {
+    let mut near = 0.0;
+    let mut far = 0.0;
+    for axis in 0..3 {
+        let (lo, hi) = (min[axis] - c[axis], max[axis] - c[axis]);
+        let nearest = if lo > 0.0 {
+            lo
+        } else if hi < 0.0 {
+            hi
+        } else {
+            0.0
+        };
+        let farthest = lo.abs().max(hi.abs());
+        near += nearest * nearest;
+        far += farthest * farthest;
+    }
+    (near.sqrt(), far.sqrt())
+}
+
+impl Function for Rbf {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        self.evaluate_f64(Vector3::new(x as f64, y as f64, z as f64)) as f32
+    }
+
+    // The kernel grows with distance, so each term lies between its values
+    // at the nearest and farthest points of the box. The linear part sees
+    // each coordinate once and is exact. Summing the terms' ranges loses
+    // the cancellation between them though, so for small boxes the mean
+    // value form from the center is much tighter, and we take both.
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        let b = [bindings[&'x'], bindings[&'y'], bindings[&'z']];
+        if b.iter().any(|i| i.is_empty()) {
+            return vec![Interval::empty()];
+        }
+
+        let min = Vector3::new(b[0].min as f64, b[1].min as f64, b[2].min as f64);
+        let max = Vector3::new(b[0].max as f64, b[1].max as f64, b[2].max as f64);
+
+        let a = &self.polynomial;
+        let mut low = a[0];
+        let mut high = a[0];
+        for axis in 0..3 {
+            let (at_min, at_max) = (a[axis + 1] * min[axis], a[axis + 1] * max[axis]);
+            low += at_min.min(at_max);
+            high += at_min.max(at_max);
+        }
+
+        for (c, w) in self.centers.iter().zip(&self.weights) {
+            let (near, far) = distance_range(min, max, *c);
+            let (a, b) = (w * kernel(near), w * kernel(far));
+            low += a.min(b);
+            high += a.max(b);
+        }
+        let natural = Interval::widened(low as f32, high as f32);
+
+        let (cx, cy, cz) = (b[0].middle(), b[1].middle(), b[2].middle());
+        let value = self.evaluate(cx, cy, cz);
+        let gradient = self.evaluate_gradient_interval(bindings);
+        let mut centered = Interval::new(value, value);
+        for (axis, &center) in [cx, cy, cz].iter().enumerate() {
+            let offset = hull(&b[axis].sub(&Interval::new(center, center)));
+            centered = hull(&centered.add(&hull(&gradient[axis][0].mul(&offset))));
+        }
+
+        vec![natural.intersect(&centered)]
+    }
+
+    fn gradient(&self, x: f32, y: f32, z: f32) -> Vector3<f32> {
+        let p = Vector3::new(x as f64, y as f64, z as f64);
+        let a = &self.polynomial;
+        let mut g = Vector3::new(a[1], a[2], a[3]);
+        for (c, w) in self.centers.iter().zip(&self.weights) {
+            // d(r^3) = 3 r (p - c)
+            g += (p - c) * (3.0 * w * distance(p, *c));
+        }
+        Vector3::new(g.x as f32, g.y as f32, g.z as f32)
+    }
+
+    fn evaluate_gradient_interval(
+        &self,
+        bindings: &HashMap<char, Interval>,
+    ) -> [Vec<Interval>; 3] {
+        let b = [bindings[&'x'], bindings[&'y'], bindings[&'z']];
+        if b.iter().any(|i| i.is_empty()) {
+            let empty = vec![Interval::empty()];
+            return [empty.clone(), empty.clone(), empty];
+        }
+
+        let min = Vector3::new(b[0].min as f64, b[1].min as f64, b[2].min as f64);
+        let max = Vector3::new(b[0].max as f64, b[1].max as f64, b[2].max as f64);
+
+        // 3 w r (p - c) per term, with r and p - c ranging independently
+        let mut low = [self.polynomial[1], self.polynomial[2], self.polynomial[3]];
+        let mut high = low;
+        for (c, w) in self.centers.iter().zip(&self.weights) {
+            let (near, far) = distance_range(min, max, *c);
+            let (r0, r1) = (3.0 * w * near, 3.0 * w * far);
+            for axis in 0..3 {
+                let (o0, o1) = (min[axis] - c[axis], max[axis] - c[axis]);
+                let products = [r0 * o0, r0 * o1, r1 * o0, r1 * o1];
+                low[axis] += products.iter().cloned().fold(::std::f64::INFINITY, f64::min);
+                high[axis] += products.iter().cloned().fold(::std::f64::NEG_INFINITY, f64::max);
+            }
+        }
+
+        let sums: Vec<Interval> = (0..3)
+            .map(|axis| Interval::widened(low[axis] as f32, high[axis] as f32))
+            .collect();
+        [vec![sums[0]], vec![sums[1]], vec![sums[2]]]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::InnerSpace;
+    use mesh_tree::MeshTree;
+    use test_util::cell;
+
+    // Evenly spread points on a sphere, with their outward normals
+    fn sphere_cloud(n: usize, radius: f32) -> (Vec<Vector3<f32>>, Vec<Vector3<f32>>) {
+        let golden = ::std::f32::consts::PI * (3.0 - 5.0f32.sqrt());
+        let normals: Vec<Vector3<f32>> = (0..n)
+            .map(|i| {
+                let z = 1.0 - 2.0 * (i as f32 + 0.5) / n as f32;
+                let r = (1.0 - z * z).sqrt();
+                let theta = golden * i as f32;
+                Vector3::new(r * theta.cos(), r * theta.sin(), z)
+            })
+            .collect();
+        let points = normals.iter().map(|n| n * radius).collect();
+        (points, normals)
+    }
+
+    #[test]
+    fn reproduces_linear() {
+        let centers = vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(0.0, 0.0, 1.0),
+            Vector3::new(1.0, 1.0, 1.0),
+            Vector3::new(0.3, 0.7, 0.2),
+        ];
+        let values: Vec<f32> = centers.iter().map(|c| 1.0 + c.x - 2.0 * c.y + 0.5 * c.z).collect();
+        let rbf = Rbf::interpolate(&centers, &values).unwrap();
+        assert_similiar!(rbf.evaluate(2.0, -1.0, 3.0), 1.0 + 2.0 + 2.0 + 1.5);
+        assert!(rbf.weights.iter().all(|w| w.abs() < 1e-6));
+    }
+
+    #[test]
+    fn singular() {
+        let centers = vec![Vector3::new(0.0, 0.0, 0.0); 5];
+        assert!(Rbf::interpolate(&centers, &[0.0; 5]).is_none());
+
+        // Four points in a plane can't pin down the linear part
+        let flat = vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(1.0, 1.0, 0.0),
+        ];
+        assert!(Rbf::interpolate(&flat, &[0.0; 4]).is_none());
+    }
+
+    #[test]
+    fn fit_sphere() {
+        let (points, normals) = sphere_cloud(60, 0.8);
+        let rbf = Rbf::fit(&points, &normals, 0.1).unwrap();
+
+        for p in &points {
+            assert!(rbf.evaluate(p.x, p.y, p.z).abs() < 1e-4);
+        }
+        assert!(rbf.evaluate(0.0, 0.0, 0.0) < 0.0);
+        assert!(rbf.evaluate(1.2, 0.3, 0.0) > 0.0);
+
+        let g = rbf.gradient(0.8, 0.0, 0.0);
+        assert!(g.normalize().x > 0.9);
+    }
+
+    #[test]
+    fn interval_bounds() {
+        let (points, normals) = sphere_cloud(40, 0.8);
+        let rbf = Rbf::fit(&points, &normals, 0.1).unwrap();
+        let boxes = [
+            cell((-1.0, 1.0), (-1.0, 1.0), (-1.0, 1.0)),
+            cell((0.6, 0.9), (-0.1, 0.2), (0.0, 0.1)),
+            cell((-0.05, 0.05), (-0.05, 0.05), (-0.05, 0.05)),
+        ];
+        for bb in &boxes {
+            let bound = hull(&rbf.evaluate_interval(&bb.bindings()));
+            let gradient = rbf.evaluate_gradient_interval(&bb.bindings());
+            for i in 0..5 {
+                for j in 0..5 {
+                    for k in 0..5 {
+                        let t = |interval: Interval, n: usize| {
+                            interval.min + interval.width() * n as f32 / 4.0
+                        };
+                        let (x, y, z) = (t(bb.x, i), t(bb.y, j), t(bb.z, k));
+                        let v = rbf.evaluate(x, y, z);
+                        assert!(v >= bound.min - 1e-4 && v <= bound.max + 1e-4);
+                        let g = rbf.gradient(x, y, z);
+                        assert!(g.y >= gradient[1][0].min - 1e-3 && g.y <= gradient[1][0].max + 1e-3);
+                    }
+                }
+            }
+        }
+
+        // The center is well inside
+        let center = rbf.evaluate_interval(&boxes[2].bindings());
+        assert!(center[0].max < 0.0);
+    }
+
+    #[test]
+    fn mesh_fit() {
+        let (points, normals) = sphere_cloud(24, 0.8);
+        let rbf = Rbf::fit(&points, &normals, 0.1).unwrap();
+        let mut tree = MeshTree::new(Box::new(rbf), cell((-1.0, 1.0), (-1.0, 1.0), (-1.0, 1.0)));
+        for _ in 0..3 {
+            tree.next_level().unwrap();
+        }
+        tree.generate_vertex_map();
+        tree.generate_triangle_set();
+        assert_eq!(tree.level, 3);
+    }
+}
@@ -0,0 +1,644 @@
+use cgmath::{InnerSpace, Vector2, Vector3};
+use function::Function;
+use interval::{hull, Interval};
+use std::collections::HashMap;
+
+// Signed distance primitives, negative inside. Where the distance is built
+// from pieces that each only see one coordinate the interval bounds are exact.
+// The cone and capsule repeat a coordinate, so theirs are looser and are
+// intersected with the bound from distance fields being 1-Lipschitz.
+//
+// Distance fields have unit gradients, so every primitive bounds its gradient
+// components by [-1, 1]. The sphere and plane do better.
+
+fn coordinates(bindings: &HashMap<char, Interval>) -> (Interval, Interval, Interval) {
+    (bindings[&'x'], bindings[&'y'], bindings[&'z'])
+}
+
+fn offset(i: Interval, c: f32) -> Interval {
+    hull(&i.sub(&Interval::new(c, c)))
+}
+
+fn length2(a: Interval, b: Interval) -> Interval {
+    hull(&a.square().add(&b.square())).sqrt()
+}
+
+fn length3(a: Interval, b: Interval, c: Interval) -> Interval {
+    let ab = hull(&a.square().add(&b.square()));
+    hull(&ab.add(&c.square())).sqrt()
+}
+
+fn scale(i: Interval, c: f32) -> Interval {
+    hull(&i.mul(&Interval::new(c, c)))
+}
+
+fn sub(a: Interval, b: Interval) -> Interval {
+    hull(&a.sub(&b))
+}
+
+fn clamp_unit(i: Interval) -> Interval {
+    Interval::new(i.min.max(0.0).min(1.0), i.max.max(0.0).min(1.0))
+}
+
+fn unit_gradient() -> [Vec<Interval>; 3] {
+    let unit = vec![Interval::new(-1.0, 1.0)];
+    [unit.clone(), unit.clone(), unit]
+}
+
+// An exact distance field changes no faster than the distance moved, so over
+// a box it stays within half the diagonal of its value at the center.
+pub fn lipschitz_interval<F: Function>(f: &F, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+    let (x, y, z) = coordinates(bindings);
+    let d = f.evaluate(x.middle(), y.middle(), z.middle());
+    let r = Vector3::new(x.width(), y.width(), z.width()).magnitude() / 2.0;
+
+    vec![Interval::widened(d - r, d + r)]
+}
+
+// Distance to a box given the per axis distance to its faces (negative
+// inside). Non-decreasing in every component.
+fn box_distance(q: Vector3<f32>) -> f32 {
+    let outside = Vector3::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).magnitude();
+    let inside = q.x.max(q.y).max(q.z).min(0.0);
+    outside + inside
+}
+
+// Same as box_distance in two dimensions, for the cylinder
+fn rect_distance(q: Vector2<f32>) -> f32 {
+    let outside = Vector2::new(q.x.max(0.0), q.y.max(0.0)).magnitude();
+    let inside = q.x.max(q.y).min(0.0);
+    outside + inside
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Sphere {
+    pub center: Vector3<f32>,
+    pub radius: f32,
+}
+
+impl Sphere {
+    pub fn new(center: Vector3<f32>, radius: f32) -> Sphere {
+        Sphere { center, radius }
+    }
+}
+
+impl Function for Sphere {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        (Vector3::new(x, y, z) - self.center).magnitude() - self.radius
+    }
+
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        let (x, y, z) = coordinates(bindings);
+        let length = length3(
+            offset(x, self.center.x),
+            offset(y, self.center.y),
+            offset(z, self.center.z),
+        );
+
+        vec![offset(length, self.radius)]
+    }
+
+    // (p - c) / |p - c|, each component of which is also in [-1, 1]
+    fn evaluate_gradient_interval(
+        &self,
+        bindings: &HashMap<char, Interval>,
+    ) -> [Vec<Interval>; 3] {
+        let (x, y, z) = coordinates(bindings);
+        let p = [
+            offset(x, self.center.x),
+            offset(y, self.center.y),
+            offset(z, self.center.z),
+        ];
+        let length = length3(p[0], p[1], p[2]);
+        let unit = Interval::new(-1.0, 1.0);
+        let component = |c: Interval| vec![hull(&c.div(&length)).intersect(&unit)];
+        [component(p[0]), component(p[1]), component(p[2])]
+    }
+}
+
+// An axis aligned box. Called Cuboid to stay clear of std's Box.
+#[derive(Copy, Clone, Debug)]
+pub struct Cuboid {
+    pub center: Vector3<f32>,
+    pub half_extents: Vector3<f32>,
+}
+
+impl Cuboid {
+    pub fn new(center: Vector3<f32>, half_extents: Vector3<f32>) -> Cuboid {
+        Cuboid {
+            center,
+            half_extents,
+        }
+    }
+
+    fn face_distances(&self, bindings: &HashMap<char, Interval>) -> [Interval; 3] {
+        let (x, y, z) = coordinates(bindings);
+        [
+            offset(offset(x, self.center.x).abs(), self.half_extents.x),
+            offset(offset(y, self.center.y).abs(), self.half_extents.y),
+            offset(offset(z, self.center.z).abs(), self.half_extents.z),
+        ]
+    }
+}
+
+impl Function for Cuboid {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        let p = Vector3::new(x, y, z) - self.center;
+        let q = Vector3::new(p.x.abs(), p.y.abs(), p.z.abs()) - self.half_extents;
+        box_distance(q)
+    }
+
+    // Each face distance depends on one coordinate and the distance is
+    // monotone in all three, so the corners of q give the exact range
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        let q = self.face_distances(bindings);
+        vec![Interval::widened(
+            box_distance(Vector3::new(q[0].min, q[1].min, q[2].min)),
+            box_distance(Vector3::new(q[0].max, q[1].max, q[2].max)),
+        )]
+    }
+
+    fn evaluate_gradient_interval(
+        &self,
+        _bindings: &HashMap<char, Interval>,
+    ) -> [Vec<Interval>; 3] {
+        unit_gradient()
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct RoundedBox {
+    pub center: Vector3<f32>,
+    pub half_extents: Vector3<f32>,
+    pub radius: f32,
+}
+
+impl RoundedBox {
+    pub fn new(center: Vector3<f32>, half_extents: Vector3<f32>, radius: f32) -> RoundedBox {
+        RoundedBox {
+            center,
+            half_extents,
+            radius,
+        }
+    }
+
+    // The rounded box is the inner box grown by the radius
+    fn inner(&self) -> Cuboid {
+        let r = Vector3::new(self.radius, self.radius, self.radius);
+        Cuboid::new(self.center, self.half_extents - r)
+    }
+}
+
+impl Function for RoundedBox {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        self.inner().evaluate(x, y, z) - self.radius
+    }
+
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        self.inner()
+            .evaluate_interval(bindings)
+            .iter()
+            .map(|i| offset(*i, self.radius))
+            .collect()
+    }
+
+    fn evaluate_gradient_interval(
+        &self,
+        _bindings: &HashMap<char, Interval>,
+    ) -> [Vec<Interval>; 3] {
+        unit_gradient()
+    }
+}
+
+// A torus around the z axis
+#[derive(Copy, Clone, Debug)]
+pub struct Torus {
+    pub center: Vector3<f32>,
+    pub major_radius: f32,
+    pub minor_radius: f32,
+}
+
+impl Torus {
+    pub fn new(center: Vector3<f32>, major_radius: f32, minor_radius: f32) -> Torus {
+        Torus {
+            center,
+            major_radius,
+            minor_radius,
+        }
+    }
+}
+
+impl Function for Torus {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        let p = Vector3::new(x, y, z) - self.center;
+        let ring = Vector2::new(p.x, p.y).magnitude() - self.major_radius;
+        Vector2::new(ring, p.z).magnitude() - self.minor_radius
+    }
+
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        let (x, y, z) = coordinates(bindings);
+        let ring = offset(
+            length2(offset(x, self.center.x), offset(y, self.center.y)),
+            self.major_radius,
+        );
+        let length = length2(ring, offset(z, self.center.z));
+
+        vec![offset(length, self.minor_radius)]
+    }
+
+    fn evaluate_gradient_interval(
+        &self,
+        _bindings: &HashMap<char, Interval>,
+    ) -> [Vec<Interval>; 3] {
+        unit_gradient()
+    }
+}
+
+// A cylinder along the z axis, capped at center.z +- half_height
+#[derive(Copy, Clone, Debug)]
+pub struct CappedCylinder {
+    pub center: Vector3<f32>,
+    pub radius: f32,
+    pub half_height: f32,
+}
+
+impl CappedCylinder {
+    pub fn new(center: Vector3<f32>, radius: f32, half_height: f32) -> CappedCylinder {
+        CappedCylinder {
+            center,
+            radius,
+            half_height,
+        }
+    }
+}
+
+impl Function for CappedCylinder {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        let p = Vector3::new(x, y, z) - self.center;
+        rect_distance(Vector2::new(
+            Vector2::new(p.x, p.y).magnitude() - self.radius,
+            p.z.abs() - self.half_height,
+        ))
+    }
+
+    // Radial and axial distances are independent and the distance is monotone
+    // in both, like the box
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        let (x, y, z) = coordinates(bindings);
+        let radial = offset(
+            length2(offset(x, self.center.x), offset(y, self.center.y)),
+            self.radius,
+        );
+        let axial = offset(offset(z, self.center.z).abs(), self.half_height);
+
+        vec![Interval::widened(
+            rect_distance(Vector2::new(radial.min, axial.min)),
+            rect_distance(Vector2::new(radial.max, axial.max)),
+        )]
+    }
+
+    fn evaluate_gradient_interval(
+        &self,
+        _bindings: &HashMap<char, Interval>,
+    ) -> [Vec<Interval>; 3] {
+        unit_gradient()
+    }
+}
+
+// A solid cone with its tip at apex, opening down the z axis with the given
+// half angle (radians) and capped at height below the tip.
+#[derive(Copy, Clone, Debug)]
+pub struct Cone {
+    pub apex: Vector3<f32>,
+    pub angle: f32,
+    pub height: f32,
+}
+
+impl Cone {
+    pub fn new(apex: Vector3<f32>, angle: f32, height: f32) -> Cone {
+        Cone {
+            apex,
+            angle,
+            height,
+        }
+    }
+}
+
+impl Function for Cone {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        let p = Vector3::new(x, y, z) - self.apex;
+        let clamp = |v: f32| v.max(0.0).min(1.0);
+
+        // Work in the plane through the axis: w is (radius, height)
+        let q = Vector2::new(self.angle.tan(), -1.0) * self.height;
+        let w = Vector2::new(Vector2::new(p.x, p.y).magnitude(), p.z);
+
+        // Closest points on the slanted side and on the base
+        let a = w - q * clamp(w.dot(q) / q.dot(q));
+        let b = w - Vector2::new(q.x * clamp(w.x / q.x), q.y);
+
+        let d = a.dot(a).min(b.dot(b));
+        let s = (w.y * q.x - w.x * q.y).max(q.y - w.y);
+        d.sqrt() * s.signum()
+    }
+
+    // The same steps as evaluate over intervals, with r and z each exact
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        let (x, y, z) = coordinates(bindings);
+        let q = Vector2::new(self.angle.tan(), -1.0) * self.height;
+        let r = length2(offset(x, self.apex.x), offset(y, self.apex.y));
+        let z = offset(z, self.apex.z);
+
+        let along = hull(&scale(r, q.x).add(&scale(z, q.y)));
+        let t = clamp_unit(scale(along, 1.0 / q.dot(q)));
+        let a = [sub(r, scale(t, q.x)), sub(z, scale(t, q.y))];
+        // r - q.x clamp(r / q.x) is max(r - q.x, 0) for r >= 0, which only
+        // uses r once
+        let rim = offset(r, q.x);
+        let b = [
+            Interval::new(rim.min.max(0.0), rim.max.max(0.0)),
+            offset(z, q.y),
+        ];
+
+        let length2 = |v: [Interval; 2]| hull(&v[0].square().add(&v[1].square()));
+        let d = length2(a).minimum(&length2(b)).sqrt();
+        let side = hull(&scale(z, q.x).sub(&scale(r, q.y)));
+        let s = side.maximum(&offset(z.neg(), -q.y));
+
+        let signed = if s.min > 0.0 {
+            d
+        } else if s.max < 0.0 {
+            d.neg()
+        } else {
+            Interval::new(-d.max, d.max)
+        };
+        vec![signed.intersect(&hull(&lipschitz_interval(self, bindings)))]
+    }
+
+    fn evaluate_gradient_interval(
+        &self,
+        _bindings: &HashMap<char, Interval>,
+    ) -> [Vec<Interval>; 3] {
+        unit_gradient()
+    }
+}
+
+// Points at distance from the plane through the origin with unit normal,
+// shifted along it by offset. Positive on the side the normal points to.
+#[derive(Copy, Clone, Debug)]
+pub struct Plane {
+    pub normal: Vector3<f32>,
+    pub offset: f32,
+}
+
+impl Plane {
+    pub fn new(normal: Vector3<f32>, offset: f32) -> Plane {
+        Plane {
+            normal: normal.normalize(),
+            offset,
+        }
+    }
+}
+
+impl Function for Plane {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        Vector3::new(x, y, z).dot(self.normal) - self.offset
+    }
+
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        let (x, y, z) = coordinates(bindings);
+        let xy = hull(&scale(x, self.normal.x).add(&scale(y, self.normal.y)));
+        let xyz = hull(&xy.add(&scale(z, self.normal.z)));
+
+        vec![offset(xyz, self.offset)]
+    }
+
+    fn evaluate_gradient_interval(
+        &self,
+        _bindings: &HashMap<char, Interval>,
+    ) -> [Vec<Interval>; 3] {
+        let n = self.normal;
+        [
+            vec![Interval::new(n.x, n.x)],
+            vec![Interval::new(n.y, n.y)],
+            vec![Interval::new(n.z, n.z)],
+        ]
+    }
+}
+
+// A segment from a to b, thickened by radius
+#[derive(Copy, Clone, Debug)]
+pub struct Capsule {
+    pub a: Vector3<f32>,
+    pub b: Vector3<f32>,
+    pub radius: f32,
+}
+
+impl Capsule {
+    pub fn new(a: Vector3<f32>, b: Vector3<f32>, radius: f32) -> Capsule {
+        Capsule { a, b, radius }
+    }
+}
+
+impl Function for Capsule {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        let pa = Vector3::new(x, y, z) - self.a;
+        let ba = self.b - self.a;
+        let h = (pa.dot(ba) / ba.dot(ba)).max(0.0).min(1.0);
+        (pa - ba * h).magnitude() - self.radius
+    }
+
+    // The closest point on the segment is a + (b - a) h for h in the clamped
+    // projection, which is exact, but the distance to it repeats h
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        let (x, y, z) = coordinates(bindings);
+        let pa = [
+            offset(x, self.a.x),
+            offset(y, self.a.y),
+            offset(z, self.a.z),
+        ];
+        let ba = self.b - self.a;
+
+        let along = hull(&scale(pa[0], ba.x).add(&scale(pa[1], ba.y)));
+        let along = hull(&along.add(&scale(pa[2], ba.z)));
+        let h = clamp_unit(scale(along, 1.0 / ba.dot(ba)));
+        let length = length3(
+            sub(pa[0], scale(h, ba.x)),
+            sub(pa[1], scale(h, ba.y)),
+            sub(pa[2], scale(h, ba.z)),
+        );
+
+        let bound = offset(length, self.radius);
+        vec![bound.intersect(&hull(&lipschitz_interval(self, bindings)))]
+    }
+
+    fn evaluate_gradient_interval(
+        &self,
+        _bindings: &HashMap<char, Interval>,
+    ) -> [Vec<Interval>; 3] {
+        unit_gradient()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mesh_tree::BoundingBox;
+    use test_util::{assert_bounds, cell};
+
+    fn boxes() -> Vec<BoundingBox> {
+        vec![
+            cell((-2.0, 2.0), (-2.0, 2.0), (-2.0, 2.0)),
+            cell((0.5, 1.5), (-0.25, 0.25), (0.0, 0.5)),
+            cell((-0.1, 0.1), (-0.1, 0.1), (-0.1, 0.1)),
+            cell((-3.0, -1.0), (0.5, 0.75), (1.0, 3.0)),
+        ]
+    }
+
+    // Small boxes straddling the surfaces of the cone and capsule
+    fn surface_boxes() -> Vec<BoundingBox> {
+        vec![
+            cell((0.4, 0.6), (-0.1, 0.1), (-0.6, -0.4)),
+            cell((0.9, 1.1), (0.0, 0.2), (-1.1, -0.9)),
+            cell((0.4, 0.6), (0.4, 0.6), (0.9, 1.1)),
+            cell((-0.1, 0.1), (-0.1, 0.1), (2.4, 2.6)),
+        ]
+    }
+
+    // At least as tight as the Lipschitz bound, and strictly tighter here
+    fn assert_tighter<F: Function>(f: &F, bb: &BoundingBox) {
+        let bound = hull(&f.evaluate_interval(&bb.bindings()));
+        let lipschitz = hull(&lipschitz_interval(f, &bb.bindings()));
+        assert!(bound.width() < lipschitz.width() * 0.9);
+    }
+
+    fn origin() -> Vector3<f32> {
+        Vector3::new(0.0, 0.0, 0.0)
+    }
+
+    #[test]
+    fn sphere() {
+        let s = Sphere::new(Vector3::new(1.0, 0.0, 0.0), 0.5);
+        assert_similiar!(s.evaluate(1.0, 0.0, 0.0), -0.5);
+        assert_similiar!(s.evaluate(2.0, 0.0, 0.0), 0.5);
+        for bb in boxes() {
+            assert_bounds(&s, &bb);
+        }
+
+        // The bound is exact
+        let r = s.evaluate_interval(&cell((2.0, 3.0), (0.0, 0.0), (0.0, 0.0)).bindings());
+        assert_similiar!(r[0].min, 0.5);
+        assert_similiar!(r[0].max, 1.5);
+    }
+
+    #[test]
+    fn cuboid() {
+        let c = Cuboid::new(origin(), Vector3::new(1.0, 0.5, 0.25));
+        assert_similiar!(c.evaluate(0.0, 0.0, 0.0), -0.25);
+        assert_similiar!(c.evaluate(2.0, 0.0, 0.0), 1.0);
+        assert_similiar!(c.evaluate(2.0, 1.5, 0.0), 2.0f32.sqrt());
+        for bb in boxes() {
+            assert_bounds(&c, &bb);
+        }
+
+        let r = c.evaluate_interval(&cell((2.0, 3.0), (0.0, 0.0), (0.0, 0.0)).bindings());
+        assert_similiar!(r[0].min, 1.0);
+        assert_similiar!(r[0].max, 2.0);
+    }
+
+    #[test]
+    fn rounded_box() {
+        let c = RoundedBox::new(origin(), Vector3::new(1.0, 1.0, 1.0), 0.25);
+        assert_similiar!(c.evaluate(2.0, 0.0, 0.0), 1.0);
+        assert_similiar!(c.evaluate(0.0, 0.0, 0.0), -1.0);
+        // The corner is rounded off
+        let corner = 0.75 + 0.25 / 3.0f32.sqrt();
+        assert_similiar!(c.evaluate(corner, corner, corner), 0.0);
+        for bb in boxes() {
+            assert_bounds(&c, &bb);
+        }
+    }
+
+    #[test]
+    fn torus() {
+        let t = Torus::new(origin(), 1.0, 0.25);
+        assert_similiar!(t.evaluate(1.0, 0.0, 0.0), -0.25);
+        assert_similiar!(t.evaluate(0.0, 0.0, 0.0), 0.75);
+        assert_similiar!(t.evaluate(0.0, 1.0, 1.0), 0.75);
+        for bb in boxes() {
+            assert_bounds(&t, &bb);
+        }
+    }
+
+    #[test]
+    fn capped_cylinder() {
+        let c = CappedCylinder::new(origin(), 0.5, 1.0);
+        assert_similiar!(c.evaluate(0.0, 0.0, 0.0), -0.5);
+        assert_similiar!(c.evaluate(1.0, 0.0, 0.0), 0.5);
+        assert_similiar!(c.evaluate(0.0, 0.0, 2.0), 1.0);
+        for bb in boxes() {
+            assert_bounds(&c, &bb);
+        }
+    }
+
+    #[test]
+    fn cone() {
+        let c = Cone::new(origin(), ::std::f32::consts::FRAC_PI_4, 1.0);
+        assert_similiar!(c.evaluate(0.0, 0.0, -2.0), 1.0);
+        assert_similiar!(c.evaluate(0.0, 0.0, 1.0), 1.0);
+        assert_similiar!(c.evaluate(0.0, 0.0, -0.5), -(0.5f32 / 2.0f32.sqrt()));
+        for bb in boxes().iter().chain(surface_boxes().iter()) {
+            assert_bounds(&c, bb);
+        }
+        assert_tighter(&c, &cell((0.1, 0.3), (0.1, 0.3), (-2.05, -1.95)));
+    }
+
+    #[test]
+    fn plane() {
+        let p = Plane::new(Vector3::new(0.0, 0.0, 2.0), 1.0);
+        assert_similiar!(p.evaluate(5.0, 5.0, 1.0), 0.0);
+        assert_similiar!(p.evaluate(5.0, 5.0, -1.0), -2.0);
+        for bb in boxes() {
+            assert_bounds(&p, &bb);
+        }
+
+        let p = Plane::new(Vector3::new(1.0, -1.0, 1.0), 0.5);
+        for bb in boxes() {
+            assert_bounds(&p, &bb);
+        }
+    }
+
+    #[test]
+    fn capsule() {
+        let c = Capsule::new(origin(), Vector3::new(0.0, 0.0, 2.0), 0.5);
+        assert_similiar!(c.evaluate(0.0, 0.0, 1.0), -0.5);
+        assert_similiar!(c.evaluate(1.0, 0.0, 1.0), 0.5);
+        assert_similiar!(c.evaluate(0.0, 0.0, 3.0), 0.5);
+        assert_similiar!(c.evaluate(0.0, 0.0, -1.0), 0.5);
+        for bb in boxes().iter().chain(surface_boxes().iter()) {
+            assert_bounds(&c, bb);
+        }
+        assert_tighter(&c, &cell((2.0, 2.2), (-0.1, 0.1), (0.0, 2.0)));
+    }
+
+    #[test]
+    fn gradient_bounds() {
+        let s = Sphere::new(Vector3::new(1.0, 0.0, 0.0), 0.5);
+        let bb = cell((2.0, 2.5), (-0.1, 0.1), (-0.1, 0.1));
+        let gradient = s.evaluate_gradient_interval(&bb.bindings());
+        assert!(hull(&gradient[0]).min > 0.6);
+        assert!(hull(&gradient[1]).max <= 0.2);
+        // The center is inside the box, so the direction could be anything
+        let around = cell((0.5, 1.5), (-1.0, 1.0), (-1.0, 1.0));
+        let gradient = s.evaluate_gradient_interval(&around.bindings());
+        assert_eq!(hull(&gradient[2]).min, -1.0);
+
+        let p = Plane::new(Vector3::new(0.0, 3.0, 4.0), 1.0);
+        let gradient = p.evaluate_gradient_interval(&bb.bindings());
+        assert_similiar!(hull(&gradient[1]).min, 0.6);
+        assert_similiar!(hull(&gradient[2]).max, 0.8);
+
+        let c = Capsule::new(origin(), Vector3::new(0.0, 0.0, 2.0), 0.5);
+        assert!(bb.lipschitz_bound(&c) <= 3.0f32.sqrt() + 1e-6);
+    }
+}
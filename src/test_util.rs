@@ -0,0 +1,39 @@
+// Helpers shared by the tests of the Function implementations
+
+use function::Function;
+use interval::{hull, Interval};
+use mesh_tree::BoundingBox;
+
+pub fn cell(x: (f32, f32), y: (f32, f32), z: (f32, f32)) -> BoundingBox {
+    BoundingBox::new(
+        Interval::new(x.0, x.1),
+        Interval::new(y.0, y.1),
+        Interval::new(z.0, z.1),
+    )
+}
+
+// Samples the box on a grid and checks every value is inside the bound
+pub fn assert_bounds<F: Function + ?Sized>(f: &F, bb: &BoundingBox) {
+    assert_bounds_within(f, bb, 1e-5);
+}
+
+pub fn assert_bounds_within<F: Function + ?Sized>(f: &F, bb: &BoundingBox, tolerance: f32) {
+    let bound = hull(&f.evaluate_interval(&bb.bindings()));
+    let steps = 8;
+    for i in 0..steps + 1 {
+        for j in 0..steps + 1 {
+            for k in 0..steps + 1 {
+                let t = |interval: Interval, n: usize| {
+                    interval.min + interval.width() * n as f32 / steps as f32
+                };
+                let v = f.evaluate(t(bb.x, i), t(bb.y, j), t(bb.z, k));
+                assert!(
+                    v >= bound.min - tolerance && v <= bound.max + tolerance,
+                    "{} outside {:?}",
+                    v,
+                    bound
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use cgmath::Vector3;
+use function::Function;
+use function_ir::Node;
+use interval::Interval;
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+// Triply periodic minimal surfaces, as their usual trigonometric level set
+// approximations with period 2 pi along each axis
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Surface {
+    Gyroid,
+    SchwarzP,
+    SchwarzD,
+    Neovius,
+}
+
+// A sheet is a wall of the given thickness either side of the surface, a
+// network fills one of the two labyrinths the surface separates, grown by
+// the thickness. Both thicknesses are in units of the level set, not length.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Variant {
+    Sheet,
+    Network,
+}
+
+fn boxed(n: Node) -> Box<Node> {
+    Box::new(n)
+}
+
+fn add(n1: Node, n2: Node) -> Node {
+    Node::Add(boxed(n1), boxed(n2))
+}
+
+fn mul(n1: Node, n2: Node) -> Node {
+    Node::Mul(boxed(n1), boxed(n2))
+}
+
+impl Surface {
+    pub fn from_name(name: &str) -> Option<Surface> {
+        match name {
+            "gyroid" => Some(Surface::Gyroid),
+            "schwarz_p" => Some(Surface::SchwarzP),
+            "schwarz_d" => Some(Surface::SchwarzD),
+            "neovius" => Some(Surface::Neovius),
+            _ => None,
+        }
+    }
+
+    // The level set at the given coordinates, zero on the surface
+    pub fn node(&self, x: Node, y: Node, z: Node) -> Node {
+        let sin = |n: &Node| Node::Sin(boxed(n.clone()));
+        let cos = |n: &Node| Node::Cos(boxed(n.clone()));
+
+        match *self {
+            Surface::Gyroid => add(
+                add(mul(sin(&x), cos(&y)), mul(sin(&y), cos(&z))),
+                mul(sin(&z), cos(&x)),
+            ),
+            Surface::SchwarzP => add(add(cos(&x), cos(&y)), cos(&z)),
+            Surface::SchwarzD => add(
+                add(
+                    mul(mul(sin(&x), sin(&y)), sin(&z)),
+                    mul(mul(sin(&x), cos(&y)), cos(&z)),
+                ),
+                add(
+                    mul(mul(cos(&x), sin(&y)), cos(&z)),
+                    mul(mul(cos(&x), cos(&y)), sin(&z)),
+                ),
+            ),
+            Surface::Neovius => add(
+                mul(Node::Constant(3.0), add(add(cos(&x), cos(&y)), cos(&z))),
+                mul(Node::Constant(4.0), mul(mul(cos(&x), cos(&y)), cos(&z))),
+            ),
+        }
+    }
+
+    // The surface repeating every `cell` along each axis, thickened into a
+    // sheet or network, negative inside. Sheets are f^2 - t^2 rather than
+    // |f| - t, the same solid but smooth everywhere.
+    pub fn lattice_node(
+        &self,
+        variant: Variant,
+        x: Node,
+        y: Node,
+        z: Node,
+        cell: Node,
+        thickness: Node,
+    ) -> Node {
+        let scale = |n: Node| {
+            Node::Div(
+                boxed(mul(Node::Constant(2.0 * PI), n)),
+                boxed(cell.clone()),
+            )
+        };
+        let f = self.node(scale(x), scale(y), scale(z));
+
+        match variant {
+            Variant::Sheet => Node::Sub(
+                boxed(mul(f.clone(), f)),
+                boxed(mul(thickness.clone(), thickness)),
+            ),
+            Variant::Network => Node::Sub(boxed(f), boxed(thickness)),
+        }
+    }
+}
+
+// A TPMS lattice filling space, with its derivatives worked out once up
+// front rather than on every gradient evaluation
+#[derive(Clone, Debug)]
+pub struct Lattice {
+    node: Node,
+    derivatives: [Node; 3],
+}
+
+impl Lattice {
+    pub fn new(surface: Surface, variant: Variant, cell_size: f32, thickness: f32) -> Lattice {
+        assert!(cell_size > 0.0, "Lattice cell size must be positive");
+        let node = surface.lattice_node(
+            variant,
+            Node::Variable('x'),
+            Node::Variable('y'),
+            Node::Variable('z'),
+            Node::Constant(cell_size),
+            Node::Constant(thickness),
+        );
+        let derivatives = [
+            node.derivative('x'),
+            node.derivative('y'),
+            node.derivative('z'),
+        ];
+
+        Lattice { node, derivatives }
+    }
+}
+
+impl Function for Lattice {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        Function::evaluate(&self.node, x, y, z)
+    }
+
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        self.node.evaluate_intervals(bindings)
+    }
+
+    fn gradient(&self, x: f32, y: f32, z: f32) -> Vector3<f32> {
+        Vector3::new(
+            Function::evaluate(&self.derivatives[0], x, y, z),
+            Function::evaluate(&self.derivatives[1], x, y, z),
+            Function::evaluate(&self.derivatives[2], x, y, z),
+        )
+    }
+
+    fn evaluate_gradient_interval(
+        &self,
+        bindings: &HashMap<char, Interval>,
+    ) -> [Vec<Interval>; 3] {
+        [
+            self.derivatives[0].evaluate_intervals(bindings),
+            self.derivatives[1].evaluate_intervals(bindings),
+            self.derivatives[2].evaluate_intervals(bindings),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mesh_tree::MeshTree;
+    use parser::parse_expression;
+    use test_util::{assert_bounds_within, cell};
+
+    fn surfaces() -> [Surface; 4] {
+        [
+            Surface::Gyroid,
+            Surface::SchwarzP,
+            Surface::SchwarzD,
+            Surface::Neovius,
+        ]
+    }
+
+    #[test]
+    fn known_values() {
+        let at = |s: Surface, x: f32, y: f32, z: f32| {
+            Function::evaluate(
+                &s.node(Node::Constant(x), Node::Constant(y), Node::Constant(z)),
+                0.0,
+                0.0,
+                0.0,
+            )
+        };
+        assert_similiar!(at(Surface::Gyroid, PI / 2.0, 0.0, 0.0), 1.0);
+        assert_similiar!(at(Surface::SchwarzP, 0.0, 0.0, 0.0), 3.0);
+        assert_similiar!(at(Surface::SchwarzD, PI / 2.0, PI / 2.0, PI / 2.0), 1.0);
+        assert_similiar!(at(Surface::Neovius, 0.0, 0.0, 0.0), 13.0);
+    }
+
+    #[test]
+    fn periodic() {
+        for &s in surfaces().iter() {
+            let f = Lattice::new(s, Variant::Network, 2.5, 0.3);
+            let (x, y, z) = (0.3, -0.7, 1.1);
+            let v = f.evaluate(x, y, z);
+            assert!((f.evaluate(x + 2.5, y, z) - v).abs() < 1e-4);
+            assert!((f.evaluate(x, y - 2.5, z) - v).abs() < 1e-4);
+            assert!((f.evaluate(x, y, z + 5.0) - v).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn sheet_and_network() {
+        // Gyroid at the origin is on the surface, so inside the sheet
+        let sheet = Lattice::new(Surface::Gyroid, Variant::Sheet, 1.0, 0.2);
+        assert_similiar!(sheet.evaluate(0.0, 0.0, 0.0), -0.04);
+        // A quarter cell along x is a peak of the level set
+        assert_similiar!(sheet.evaluate(0.25, 0.0, 0.0), 1.0 - 0.04);
+
+        let network = Lattice::new(Surface::SchwarzP, Variant::Network, 1.0, 0.5);
+        assert_similiar!(network.evaluate(0.5, 0.5, 0.5), -3.5);
+        assert_similiar!(network.evaluate(0.0, 0.0, 0.0), 2.5);
+
+        // Gradient against central differences
+        let g = network.gradient(0.1, 0.2, 0.3);
+        let h = 1e-3;
+        let fd = (network.evaluate(0.1 + h, 0.2, 0.3) - network.evaluate(0.1 - h, 0.2, 0.3))
+            / (2.0 * h);
+        assert!((g.x - fd).abs() < 1e-2);
+    }
+
+    #[test]
+    fn interval_bounds() {
+        let boxes = [
+            cell((0.0, 0.1), (0.2, 0.3), (-0.1, 0.0)),
+            cell((-1.0, 1.0), (-1.0, 1.0), (-1.0, 1.0)),
+            cell((0.4, 0.45), (0.1, 0.15), (0.7, 0.75)),
+        ];
+        for &s in surfaces().iter() {
+            for &variant in [Variant::Sheet, Variant::Network].iter() {
+                let f = Lattice::new(s, variant, 1.0, 0.2);
+                for bb in &boxes {
+                    assert_bounds_within(&f, bb, 1e-4);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parse_lattices() {
+        let input: Vec<char> = "gyroid(x, y, z, 2, 0.3)".chars().collect();
+        let parsed = parse_expression(&input, 0).unwrap();
+        let lattice = Lattice::new(Surface::Gyroid, Variant::Sheet, 2.0, 0.3);
+        assert_similiar!(
+            Function::evaluate(&*parsed, 0.3, 0.4, 0.5),
+            lattice.evaluate(0.3, 0.4, 0.5)
+        );
+
+        let input: Vec<char> = "neovius_network(x, y, z, 1.5, 0.1)".chars().collect();
+        let parsed = parse_expression(&input, 0).unwrap();
+        let lattice = Lattice::new(Surface::Neovius, Variant::Network, 1.5, 0.1);
+        assert_similiar!(
+            Function::evaluate(&*parsed, -0.2, 0.4, 0.9),
+            lattice.evaluate(-0.2, 0.4, 0.9)
+        );
+    }
+
+    #[test]
+    fn mesh_gyroid() {
+        let f = Lattice::new(Surface::Gyroid, Variant::Sheet, 1.0, 0.3);
+        let mut tree = MeshTree::new(Box::new(f), cell((-0.5, 0.5), (-0.5, 0.5), (-0.5, 0.5)));
+        for _ in 0..3 {
+            tree.next_level().unwrap();
+        }
+        tree.generate_vertex_map();
+        tree.generate_triangle_set();
+        assert!(!tree.geometry().triangles.is_empty());
+    }
+}
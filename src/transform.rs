@@ -0,0 +1,238 @@
+use cgmath::{InnerSpace, Matrix, Matrix3, Matrix4, SquareMatrix, Vector3, Vector4};
+use function::Function;
+use interval::{hull, Interval};
+use std::collections::HashMap;
+
+// Moves a function around by an affine transform. We store the inverse, since
+// evaluating means pulling the query point back into the function's own space.
+#[derive(Copy, Clone, Debug)]
+pub struct Transform<F> {
+    pub function: F,
+    inverse: Matrix4<f32>,
+    // Uniform scaling also scales distances, so the output is multiplied back
+    // up to keep distance fields as distances
+    scale: f32,
+}
+
+impl<F: Function> Transform<F> {
+    // A general affine transform. None if it can't be inverted.
+    pub fn new(function: F, transform: Matrix4<f32>) -> Option<Transform<F>> {
+        transform.invert().map(|inverse| Transform {
+            function,
+            inverse,
+            scale: 1.0,
+        })
+    }
+
+    pub fn translate(function: F, offset: Vector3<f32>) -> Transform<F> {
+        Transform {
+            function,
+            inverse: Matrix4::from_translation(-offset),
+            scale: 1.0,
+        }
+    }
+
+    pub fn scale(function: F, factor: f32) -> Transform<F> {
+        assert!(factor > 0.0, "Scale factor must be positive");
+        Transform {
+            function,
+            inverse: Matrix4::from_scale(1.0 / factor),
+            scale: factor,
+        }
+    }
+
+    // Rotation must be orthonormal, its inverse is its transpose
+    pub fn rotate(function: F, rotation: Matrix3<f32>) -> Transform<F> {
+        Transform {
+            function,
+            inverse: Matrix4::from(rotation.transpose()),
+            scale: 1.0,
+        }
+    }
+
+    // Reflects across the plane through the origin with the given normal. A
+    // reflection is its own inverse.
+    pub fn mirror(function: F, normal: Vector3<f32>) -> Transform<F> {
+        let n = normal.normalize();
+        let reflection = Matrix3::from_cols(
+            Vector3::unit_x() - n * (2.0 * n.x),
+            Vector3::unit_y() - n * (2.0 * n.y),
+            Vector3::unit_z() - n * (2.0 * n.z),
+        );
+
+        Transform {
+            function,
+            inverse: Matrix4::from(reflection),
+            scale: 1.0,
+        }
+    }
+
+    fn local_point(&self, x: f32, y: f32, z: f32) -> Vector3<f32> {
+        (self.inverse * Vector4::new(x, y, z, 1.0)).truncate()
+    }
+
+    // The axis aligned bounds of the box after pulling it back. Each output
+    // coordinate is a linear combination of the inputs, each appearing once,
+    // so interval arithmetic gives exactly the bounding box of the rotated box.
+    fn local_bindings(&self, bindings: &HashMap<char, Interval>) -> HashMap<char, Interval> {
+        let inputs = [bindings[&'x'], bindings[&'y'], bindings[&'z']];
+        let m = &self.inverse;
+
+        let row = |i: usize| {
+            let mut sum = Interval::new(m[3][i], m[3][i]);
+            for j in 0..3 {
+                let term = hull(&inputs[j].mul(&Interval::new(m[j][i], m[j][i])));
+                sum = hull(&sum.add(&term));
+            }
+            sum
+        };
+
+        let mut local = bindings.clone();
+        local.insert('x', row(0));
+        local.insert('y', row(1));
+        local.insert('z', row(2));
+        local
+    }
+
+    // Pulls a gradient back through the transform: the transpose of the
+    // inverse applied to the local gradient, times the scale
+    fn world_gradient(&self, local: Vector3<f32>) -> Vector3<f32> {
+        let m = &self.inverse;
+        Vector3::new(
+            m[0].truncate().dot(local),
+            m[1].truncate().dot(local),
+            m[2].truncate().dot(local),
+        ) * self.scale
+    }
+}
+
+impl<F: Function> Function for Transform<F> {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        let p = self.local_point(x, y, z);
+        self.function.evaluate(p.x, p.y, p.z) * self.scale
+    }
+
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        let scale = Interval::new(self.scale, self.scale);
+        self.function
+            .evaluate_interval(&self.local_bindings(bindings))
+            .iter()
+            .flat_map(|interval| interval.mul(&scale))
+            .collect()
+    }
+
+    fn gradient(&self, x: f32, y: f32, z: f32) -> Vector3<f32> {
+        let p = self.local_point(x, y, z);
+        self.world_gradient(self.function.gradient(p.x, p.y, p.z))
+    }
+
+    fn evaluate_gradient_interval(
+        &self,
+        bindings: &HashMap<char, Interval>,
+    ) -> [Vec<Interval>; 3] {
+        let local = self
+            .function
+            .evaluate_gradient_interval(&self.local_bindings(bindings));
+        let local = [hull(&local[0]), hull(&local[1]), hull(&local[2])];
+        let m = &self.inverse;
+
+        let column = |j: usize| {
+            let mut sum = Interval::new(0.0, 0.0);
+            for i in 0..3 {
+                let coefficient = m[j][i] * self.scale;
+                let term = hull(&local[i].mul(&Interval::new(coefficient, coefficient)));
+                sum = hull(&sum.add(&term));
+            }
+            vec![sum]
+        };
+
+        [column(0), column(1), column(2)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Rad;
+    use parser::parse_expression;
+    use sdf::{Cuboid, Sphere};
+    use test_util::{assert_bounds, cell};
+
+    fn unit_sphere() -> Sphere {
+        Sphere::new(Vector3::new(0.0, 0.0, 0.0), 1.0)
+    }
+
+    #[test]
+    fn translate() {
+        let t = Transform::translate(unit_sphere(), Vector3::new(2.0, 0.0, 0.0));
+        assert_similiar!(t.evaluate(2.0, 0.0, 0.0), -1.0);
+        assert_similiar!(t.evaluate(0.0, 0.0, 0.0), 1.0);
+
+        let r = t.evaluate_interval(&cell((3.0, 4.0), (0.0, 0.0), (0.0, 0.0)).bindings());
+        assert_similiar!(r[0].min, 0.0);
+        assert_similiar!(r[0].max, 1.0);
+    }
+
+    #[test]
+    fn scale() {
+        let s = Transform::scale(unit_sphere(), 2.0);
+        assert_similiar!(s.evaluate(0.0, 0.0, 0.0), -2.0);
+        assert_similiar!(s.evaluate(3.0, 0.0, 0.0), 1.0);
+        assert_bounds(&s, &cell((0.5, 3.0), (-1.0, 0.0), (0.2, 0.4)));
+    }
+
+    #[test]
+    fn rotate() {
+        let cube = Cuboid::new(Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.5, 0.1, 0.1));
+        let r = Transform::rotate(cube, Matrix3::from_angle_z(Rad(::std::f32::consts::FRAC_PI_2)));
+
+        // The bar along x now lies along y
+        assert!(r.evaluate(0.0, 1.0, 0.0) < 0.0);
+        assert!(r.evaluate(1.0, 0.0, 0.0) > 0.0);
+
+        let r = Transform::rotate(cube, Matrix3::from_angle_z(Rad(0.3)));
+        assert_bounds(&r, &cell((0.0, 1.0), (0.0, 1.0), (-0.2, 0.2)));
+        assert_bounds(&r, &cell((-2.0, 2.0), (-2.0, 2.0), (-2.0, 2.0)));
+    }
+
+    #[test]
+    fn mirror() {
+        let s = Sphere::new(Vector3::new(1.0, 0.0, 0.0), 0.5);
+        let m = Transform::mirror(s, Vector3::new(1.0, 0.0, 0.0));
+        assert_similiar!(m.evaluate(-1.0, 0.0, 0.0), -0.5);
+        assert_similiar!(m.evaluate(1.0, 0.0, 0.0), 1.5);
+        assert_bounds(&m, &cell((-1.5, -0.5), (-0.5, 0.5), (0.0, 0.5)));
+    }
+
+    #[test]
+    fn gradient() {
+        let cube = Cuboid::new(Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.5, 0.1, 0.1));
+        let r = Transform::rotate(cube, Matrix3::from_angle_z(Rad(::std::f32::consts::FRAC_PI_2)));
+        // The +x face of the bar now faces +y
+        let g = r.gradient(0.0, 1.7, 0.0);
+        assert!(g.x.abs() < 1e-2 && (g.y - 1.0).abs() < 1e-2);
+
+        let s = Transform::scale(unit_sphere(), 2.0);
+        let g = s.gradient(3.0, 0.0, 0.0);
+        assert!((g.x - 1.0).abs() < 1e-2);
+
+        let input: Vec<char> = "x^2 + y^2 + z^2 - 1".chars().collect();
+        let sphere = *parse_expression(&input, 0).unwrap();
+        let t = Transform::translate(sphere, Vector3::new(2.0, 0.0, 0.0));
+        assert_similiar!(t.gradient(3.0, 0.0, 0.0).x, 2.0);
+        let bounds = t.evaluate_gradient_interval(&cell((2.5, 3.0), (0.0, 1.0), (0.0, 0.0)).bindings());
+        assert_similiar!(bounds[0][0].min, 1.0);
+        assert_similiar!(bounds[0][0].max, 2.0);
+    }
+
+    #[test]
+    fn affine() {
+        let shear = Matrix4::new(
+            1.0, 0.0, 0.0, 0.0, 0.5, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+        );
+        let t = Transform::new(unit_sphere(), shear).unwrap();
+        assert_bounds(&t, &cell((-1.0, 1.0), (0.0, 2.0), (-0.5, 0.5)));
+
+        assert!(Transform::new(unit_sphere(), Matrix4::from_scale(0.0)).is_none());
+    }
+}
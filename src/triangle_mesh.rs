@@ -0,0 +1,612 @@
+use cgmath::{InnerSpace, Vector3};
+use function::Function;
+use interval::Interval;
+use sdf::lipschitz_interval;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use triangle_mesh_error::{MeshError, MeshResult};
+
+// Triangles per BVH leaf
+const LEAF_SIZE: usize = 4;
+
+// Which part of a triangle a closest point lies on. Edge k runs from corner
+// k to corner k + 1.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Feature {
+    Face,
+    Edge(usize),
+    Vertex(usize),
+}
+
+// Closest point on the triangle abc to p, after Ericson's Real-Time Collision
+// Detection, 5.1.5
+fn closest_point(
+    p: Vector3<f32>,
+    a: Vector3<f32>,
+    b: Vector3<f32>,
+    c: Vector3<f32>,
+) -> (Vector3<f32>, Feature) {
+    let (ab, ac) = (b - a, c - a);
+
+    let ap = p - a;
+    let (d1, d2) = (ab.dot(ap), ac.dot(ap));
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return (a, Feature::Vertex(0));
+    }
+
+    let bp = p - b;
+    let (d3, d4) = (ab.dot(bp), ac.dot(bp));
+    if d3 >= 0.0 && d4 <= d3 {
+        return (b, Feature::Vertex(1));
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return (a + ab * (d1 / (d1 - d3)), Feature::Edge(0));
+    }
+
+    let cp = p - c;
+    let (d5, d6) = (ab.dot(cp), ac.dot(cp));
+    if d6 >= 0.0 && d5 <= d6 {
+        return (c, Feature::Vertex(2));
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return (a + ac * (d2 / (d2 - d6)), Feature::Edge(2));
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+        return (b + (c - b) * w, Feature::Edge(1));
+    }
+
+    let denom = 1.0 / (va + vb + vc);
+    (a + ab * (vb * denom) + ac * (vc * denom), Feature::Face)
+}
+
+#[derive(Debug, Clone)]
+enum BvhContents {
+    Leaf { start: usize, end: usize },
+    Inner { left: usize, right: usize },
+}
+
+#[derive(Debug, Clone)]
+struct BvhNode {
+    min: Vector3<f32>,
+    max: Vector3<f32>,
+    contents: BvhContents,
+}
+
+impl BvhNode {
+    fn distance2(&self, p: Vector3<f32>) -> f32 {
+        let d = Vector3::new(
+            (self.min.x - p.x).max(p.x - self.max.x).max(0.0),
+            (self.min.y - p.y).max(p.y - self.max.y).max(0.0),
+            (self.min.z - p.z).max(p.z - self.max.z).max(0.0),
+        );
+        d.magnitude2()
+    }
+}
+
+// Signed distance to a closed, consistently wound (counter clockwise seen from
+// outside) triangle mesh. The sign comes from angle weighted pseudonormals
+// (Baerentzen and Aanaes), which get it right even when the closest point is
+// on an edge or a vertex.
+#[derive(Debug, Clone)]
+pub struct TriangleMesh {
+    vertices: Vec<Vector3<f32>>,
+    triangles: Vec<[usize; 3]>,
+    face_normals: Vec<Vector3<f32>>,
+    edge_normals: Vec<[Vector3<f32>; 3]>,
+    vertex_normals: Vec<Vector3<f32>>,
+    // Triangle indices in BVH leaf order
+    order: Vec<usize>,
+    nodes: Vec<BvhNode>,
+}
+
+impl TriangleMesh {
+    // Degenerate triangles are dropped, they have no normal to sign with
+    pub fn new(vertices: Vec<Vector3<f32>>, triangles: Vec<[usize; 3]>) -> MeshResult<TriangleMesh> {
+        for (face, triangle) in triangles.iter().enumerate() {
+            if let Some(&index) = triangle.iter().find(|&&i| i >= vertices.len()) {
+                return Err(MeshError::IndexOutOfRange { face, index });
+            }
+        }
+
+        let triangles: Vec<[usize; 3]> = triangles
+            .into_iter()
+            .filter(|t| {
+                let (a, b, c) = (vertices[t[0]], vertices[t[1]], vertices[t[2]]);
+                (b - a).cross(c - a).magnitude2() > 0.0
+            })
+            .collect();
+        if triangles.is_empty() {
+            return Err(MeshError::Empty);
+        }
+
+        let mut mesh = TriangleMesh {
+            vertices,
+            triangles,
+            face_normals: Vec::new(),
+            edge_normals: Vec::new(),
+            vertex_normals: Vec::new(),
+            order: Vec::new(),
+            nodes: Vec::new(),
+        };
+        mesh.compute_normals();
+
+        let mut order: Vec<usize> = (0..mesh.triangles.len()).collect();
+        mesh.build_bvh(&mut order[..], 0);
+        mesh.order = order;
+        Ok(mesh)
+    }
+
+    // Loads an OBJ or STL file, going by the extension
+    pub fn load(path: &Path) -> MeshResult<TriangleMesh> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .unwrap_or_default();
+
+        match extension.as_str() {
+            "obj" => TriangleMesh::from_obj(BufReader::new(File::open(path)?)),
+            "stl" => TriangleMesh::from_stl(File::open(path)?),
+            _ => Err(MeshError::UnsupportedFormat(extension)),
+        }
+    }
+
+    // Vertices and faces only. Polygons are split into fans.
+    pub fn from_obj<R: BufRead>(reader: R) -> MeshResult<TriangleMesh> {
+        let mut vertices = Vec::new();
+        let mut triangles = Vec::new();
+
+        for (n, line) in reader.lines().enumerate() {
+            let line = line?;
+            let error = |message: String| MeshError::Parse {
+                line: n + 1,
+                message,
+            };
+            let mut tokens = line.split_whitespace();
+
+            match tokens.next() {
+                Some("v") => {
+                    let mut coordinates = [0.0; 3];
+                    for c in coordinates.iter_mut() {
+                        *c = tokens
+                            .next()
+                            .ok_or_else(|| error("Vertex needs three coordinates".to_string()))?
+                            .parse()
+                            .map_err(|e| error(format!("{}", e)))?;
+                    }
+                    vertices.push(Vector3::new(coordinates[0], coordinates[1], coordinates[2]));
+                }
+                Some("f") => {
+                    let mut face = Vec::new();
+                    for token in tokens {
+                        // Only the position index of v/vt/vn matters here
+                        let index: i64 = token
+                            .split('/')
+                            .next()
+                            .unwrap()
+                            .parse()
+                            .map_err(|e| error(format!("{}", e)))?;
+                        // Negative indices count back from the latest vertex
+                        let index = if index < 0 {
+                            vertices.len() as i64 + index
+                        } else {
+                            index - 1
+                        };
+                        if index < 0 || index as usize >= vertices.len() {
+                            return Err(error(format!("Vertex index {} out of range", token)));
+                        }
+                        face.push(index as usize);
+                    }
+
+                    if face.len() < 3 {
+                        return Err(error("Face needs at least three vertices".to_string()));
+                    }
+                    for i in 1..face.len() - 1 {
+                        triangles.push([face[0], face[i], face[i + 1]]);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        TriangleMesh::new(vertices, triangles)
+    }
+
+    // ASCII or binary STL. Binary files are recognized by their size matching
+    // the triangle count in the header, since they may start with "solid" too.
+    pub fn from_stl<R: Read>(mut reader: R) -> MeshResult<TriangleMesh> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+
+        let corners = if bytes.len() >= 84
+            && 84 + 50 * read_u32(&bytes[80..84]) as usize == bytes.len()
+        {
+            stl_binary_corners(&bytes)
+        } else {
+            stl_ascii_corners(&String::from_utf8_lossy(&bytes))?
+        };
+
+        // STL repeats every corner, but the pseudonormals need to know which
+        // triangles share edges and vertices
+        let mut vertices = Vec::new();
+        let mut indices = HashMap::new();
+        let mut triangles = Vec::new();
+        for triangle in corners.chunks(3) {
+            let mut t = [0; 3];
+            for (i, v) in triangle.iter().enumerate() {
+                let key = [v.x.to_bits(), v.y.to_bits(), v.z.to_bits()];
+                t[i] = *indices.entry(key).or_insert_with(|| {
+                    vertices.push(*v);
+                    vertices.len() - 1
+                });
+            }
+            triangles.push(t);
+        }
+
+        TriangleMesh::new(vertices, triangles)
+    }
+
+    fn compute_normals(&mut self) {
+        let mut vertex_normals = vec![Vector3::new(0.0, 0.0, 0.0); self.vertices.len()];
+        let mut edges: HashMap<(usize, usize), Vector3<f32>> = HashMap::new();
+        let edge_key = |a: usize, b: usize| (a.min(b), a.max(b));
+
+        for t in &self.triangles {
+            let corners = [self.vertices[t[0]], self.vertices[t[1]], self.vertices[t[2]]];
+            let normal = (corners[1] - corners[0])
+                .cross(corners[2] - corners[0])
+                .normalize();
+            self.face_normals.push(normal);
+
+            for i in 0..3 {
+                let e1 = (corners[(i + 1) % 3] - corners[i]).normalize();
+                let e2 = (corners[(i + 2) % 3] - corners[i]).normalize();
+                let angle = e1.dot(e2).max(-1.0).min(1.0).acos();
+                vertex_normals[t[i]] += normal * angle;
+
+                *edges
+                    .entry(edge_key(t[i], t[(i + 1) % 3]))
+                    .or_insert(Vector3::new(0.0, 0.0, 0.0)) += normal;
+            }
+        }
+
+        self.edge_normals = self
+            .triangles
+            .iter()
+            .map(|t| {
+                [
+                    edges[&edge_key(t[0], t[1])],
+                    edges[&edge_key(t[1], t[2])],
+                    edges[&edge_key(t[2], t[0])],
+                ]
+            })
+            .collect();
+        self.vertex_normals = vertex_normals;
+    }
+
+    fn centroid(&self, t: usize) -> Vector3<f32> {
+        let t = self.triangles[t];
+        (self.vertices[t[0]] + self.vertices[t[1]] + self.vertices[t[2]]) / 3.0
+    }
+
+    // Splits at the median centroid along the longest axis of the bounds.
+    // Returns the index of the new node.
+    fn build_bvh(&mut self, order: &mut [usize], start: usize) -> usize {
+        let mut min = Vector3::new(::std::f32::INFINITY, ::std::f32::INFINITY, ::std::f32::INFINITY);
+        let mut max = -min;
+        for &t in order.iter() {
+            for &v in &self.triangles[t] {
+                let p = self.vertices[v];
+                min = Vector3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+                max = Vector3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+            }
+        }
+
+        let index = self.nodes.len();
+        self.nodes.push(BvhNode {
+            min,
+            max,
+            contents: BvhContents::Leaf {
+                start,
+                end: start + order.len(),
+            },
+        });
+        if order.len() <= LEAF_SIZE {
+            return index;
+        }
+
+        let size = max - min;
+        let axis = if size.x >= size.y && size.x >= size.z {
+            0
+        } else if size.y >= size.z {
+            1
+        } else {
+            2
+        };
+        order.sort_by(|&a, &b| {
+            self.centroid(a)[axis]
+                .partial_cmp(&self.centroid(b)[axis])
+                .unwrap_or(::std::cmp::Ordering::Equal)
+        });
+
+        let middle = order.len() / 2;
+        let (lower, upper) = order.split_at_mut(middle);
+        let left = self.build_bvh(lower, start);
+        let right = self.build_bvh(upper, start + middle);
+        self.nodes[index].contents = BvhContents::Inner { left, right };
+        index
+    }
+
+    fn pseudonormal(&self, t: usize, feature: Feature) -> Vector3<f32> {
+        match feature {
+            Feature::Face => self.face_normals[t],
+            Feature::Edge(i) => self.edge_normals[t][i],
+            Feature::Vertex(i) => self.vertex_normals[self.triangles[t][i]],
+        }
+    }
+
+    // Squared distance to the closest point on the mesh, and the vector from
+    // that point to p dotted with its pseudonormal
+    fn nearest(&self, p: Vector3<f32>) -> (f32, f32) {
+        let mut best = (::std::f32::INFINITY, 0.0);
+        let mut stack = vec![0];
+
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n];
+            if node.distance2(p) >= best.0 {
+                continue;
+            }
+
+            match node.contents {
+                BvhContents::Leaf { start, end } => for &t in &self.order[start..end] {
+                    let corners = self.triangles[t];
+                    let (q, feature) = closest_point(
+                        p,
+                        self.vertices[corners[0]],
+                        self.vertices[corners[1]],
+                        self.vertices[corners[2]],
+                    );
+                    let d2 = (p - q).magnitude2();
+                    if d2 < best.0 {
+                        best = (d2, (p - q).dot(self.pseudonormal(t, feature)));
+                    }
+                },
+                BvhContents::Inner { left, right } => {
+                    // Visit the nearer child first so the other is more
+                    // likely to be pruned
+                    if self.nodes[left].distance2(p) < self.nodes[right].distance2(p) {
+                        stack.push(right);
+                        stack.push(left);
+                    } else {
+                        stack.push(left);
+                        stack.push(right);
+                    }
+                }
+            }
+        }
+
+        best
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16 | (bytes[3] as u32) << 24
+}
+
+fn read_f32(bytes: &[u8]) -> f32 {
+    f32::from_bits(read_u32(bytes))
+}
+
+// 80 byte header, triangle count, then per triangle a normal, three corners
+// and two bytes of attributes
+fn stl_binary_corners(bytes: &[u8]) -> Vec<Vector3<f32>> {
+    let count = read_u32(&bytes[80..84]) as usize;
+    let mut corners = Vec::with_capacity(3 * count);
+    for t in 0..count {
+        let facet = &bytes[84 + 50 * t..84 + 50 * (t + 1)];
+        for i in 0..3 {
+            let v = &facet[12 * (i + 1)..12 * (i + 2)];
+            corners.push(Vector3::new(read_f32(&v[0..4]), read_f32(&v[4..8]), read_f32(&v[8..12])));
+        }
+    }
+    corners
+}
+
+// Only the vertex lines matter, everything else is structure
+fn stl_ascii_corners(text: &str) -> MeshResult<Vec<Vector3<f32>>> {
+    let mut corners = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let mut tokens = line.split_whitespace();
+        if tokens.next() != Some("vertex") {
+            continue;
+        }
+
+        let coordinates: Result<Vec<f32>, _> = tokens.map(|t| t.parse()).collect();
+        match coordinates {
+            Ok(ref c) if c.len() == 3 => corners.push(Vector3::new(c[0], c[1], c[2])),
+            _ => {
+                return Err(MeshError::Parse {
+                    line: n + 1,
+                    message: "Vertex needs three coordinates".to_string(),
+                })
+            }
+        }
+    }
+
+    if corners.len() % 3 != 0 {
+        return Err(MeshError::Parse {
+            line: text.lines().count(),
+            message: "Facet needs three vertices".to_string(),
+        });
+    }
+    Ok(corners)
+}
+
+impl Function for TriangleMesh {
+    fn evaluate(&self, x: f32, y: f32, z: f32) -> f32 {
+        let (distance2, side) = self.nearest(Vector3::new(x, y, z));
+        if side < 0.0 {
+            -distance2.sqrt()
+        } else {
+            distance2.sqrt()
+        }
+    }
+
+    fn evaluate_interval(&self, bindings: &HashMap<char, Interval>) -> Vec<Interval> {
+        lipschitz_interval(self, bindings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use csg::Difference;
+    use mesh_tree::MeshTree;
+    use sdf::{Cuboid, Sphere};
+    use std::io::Cursor;
+    use test_util::{assert_bounds_within, cell};
+
+    const CUBE_OBJ: &str = "
+# A cube from -1 to 1, wound outwards
+v -1 -1 -1
+v 1 -1 -1
+v 1 1 -1
+v -1 1 -1
+v -1 -1 1
+v 1 -1 1
+v 1 1 1
+v -1 1 1
+f 1 4 3 2
+f 5 6 7 8
+f 1/1 2/2 6/3 5/4
+f 3//1 4//1 8//1 7//1
+f -8 -4 -1 -5
+f 2 3 7 6
+";
+
+    fn cube() -> TriangleMesh {
+        TriangleMesh::from_obj(Cursor::new(CUBE_OBJ)).unwrap()
+    }
+
+    #[test]
+    fn closest_point_features() {
+        let (a, b, c) = (
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        );
+        let (q, f) = closest_point(Vector3::new(0.2, 0.2, 1.0), a, b, c);
+        assert_eq!(f, Feature::Face);
+        assert_similiar!(q.x, 0.2);
+        assert_eq!(closest_point(Vector3::new(-1.0, -1.0, 0.0), a, b, c).1, Feature::Vertex(0));
+        assert_eq!(closest_point(Vector3::new(0.5, -1.0, 0.0), a, b, c).1, Feature::Edge(0));
+        assert_eq!(closest_point(Vector3::new(1.0, 1.0, 0.0), a, b, c).1, Feature::Edge(1));
+        assert_eq!(closest_point(Vector3::new(-1.0, 0.5, 0.0), a, b, c).1, Feature::Edge(2));
+        assert_eq!(closest_point(Vector3::new(0.0, 3.0, 0.0), a, b, c).1, Feature::Vertex(2));
+    }
+
+    #[test]
+    fn matches_cube_distance() {
+        let mesh = cube();
+        let exact = Cuboid::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 1.0, 1.0));
+        for i in 0..7 {
+            for j in 0..7 {
+                for k in 0..7 {
+                    let p = |n: usize| -2.0 + n as f32 * 0.6 + 0.05;
+                    let (x, y, z) = (p(i), p(j), p(k));
+                    assert!((mesh.evaluate(x, y, z) - exact.evaluate(x, y, z)).abs() < 1e-5);
+                }
+            }
+        }
+
+        // Closest to an edge and to a corner
+        assert_similiar!(mesh.evaluate(2.0, 2.0, 0.0), 2.0f32.sqrt());
+        assert_similiar!(mesh.evaluate(2.0, 2.0, 2.0), 3.0f32.sqrt());
+        assert_similiar!(mesh.evaluate(0.9, 0.9, 0.9), -0.1);
+    }
+
+    #[test]
+    fn stl() {
+        let mesh = cube();
+        let mut ascii = String::from("solid cube\n");
+        let mut binary = vec![0u8; 80];
+        binary.extend_from_slice(&(mesh.triangles.len() as u32).to_le_bytes());
+        for t in &mesh.triangles {
+            ascii.push_str("facet normal 0 0 0\nouter loop\n");
+            binary.extend_from_slice(&[0u8; 12]);
+            for &v in t {
+                let p = mesh.vertices[v];
+                ascii.push_str(&format!("vertex {} {} {}\n", p.x, p.y, p.z));
+                for c in &[p.x, p.y, p.z] {
+                    binary.extend_from_slice(&c.to_bits().to_le_bytes());
+                }
+            }
+            ascii.push_str("endloop\nendfacet\n");
+            binary.extend_from_slice(&[0u8; 2]);
+        }
+        ascii.push_str("endsolid cube\n");
+
+        for loaded in &[
+            TriangleMesh::from_stl(Cursor::new(ascii)).unwrap(),
+            TriangleMesh::from_stl(Cursor::new(binary)).unwrap(),
+        ] {
+            assert_eq!(loaded.vertices.len(), 8);
+            assert_similiar!(loaded.evaluate(0.0, 0.0, 0.0), -1.0);
+            assert_similiar!(loaded.evaluate(2.0, 2.0, 2.0), 3.0f32.sqrt());
+        }
+    }
+
+    #[test]
+    fn errors() {
+        match TriangleMesh::from_obj(Cursor::new("v 0 0 0\nv 1 0 0\nf 1 2 3\n")) {
+            Err(MeshError::Parse { line: 3, .. }) => (),
+            result => panic!("Expected parse error, found {:?}", result),
+        }
+        match TriangleMesh::from_obj(Cursor::new("v 0 0 0\n")) {
+            Err(MeshError::Empty) => (),
+            result => panic!("Expected empty mesh, found {:?}", result),
+        }
+        let vertices = vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        ];
+        match TriangleMesh::new(vertices, vec![[0, 1, 2], [0, 2, 3]]) {
+            Err(MeshError::IndexOutOfRange { face: 1, index: 3 }) => (),
+            result => panic!("Expected index out of range, found {:?}", result),
+        }
+        match TriangleMesh::load(Path::new("mesh.ply")) {
+            Err(MeshError::UnsupportedFormat(ref e)) if e == "ply" => (),
+            result => panic!("Expected unsupported format, found {:?}", result),
+        }
+    }
+
+    #[test]
+    fn mesh_with_csg() {
+        let mesh = cube();
+        let bb = cell((-0.3, 0.7), (0.8, 1.4), (-2.0, -0.5));
+        assert_bounds_within(&mesh, &bb, 0.0);
+
+        let scene = Difference::new(mesh, Sphere::new(Vector3::new(1.0, 1.0, 1.0), 0.8));
+        assert!(scene.evaluate(0.9, 0.9, 0.9) > 0.0);
+        assert!(scene.evaluate(-0.9, -0.9, -0.9) < 0.0);
+
+        let bb = cell((-1.5, 1.5), (-1.5, 1.5), (-1.5, 1.5));
+        let mut tree = MeshTree::new(Box::new(scene), bb);
+        for _ in 0..3 {
+            tree.next_level().unwrap();
+        }
+        tree.generate_vertex_map();
+        tree.generate_triangle_set();
+        assert_eq!(tree.level, 3);
+    }
+}
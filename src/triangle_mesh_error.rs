@@ -0,0 +1,48 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum MeshError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+    // A triangle refers to a vertex past the end of the vertex list
+    IndexOutOfRange { face: usize, index: usize },
+    UnsupportedFormat(String),
+    Empty,
+}
+
+impl Error for MeshError {
+    fn description(&self) -> &str {
+        "An error occured while loading the mesh"
+    }
+
+    fn cause(&self) -> Option<&dyn Error> {
+        match self {
+            &MeshError::Io(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &MeshError::Io(ref err) => write!(f, "{}", err),
+            &MeshError::Parse { line, ref message } => write!(f, "{} on line {}", message, line),
+            &MeshError::IndexOutOfRange { face, index } => {
+                write!(f, "Vertex index {} out of range in face {}", index, face)
+            }
+            &MeshError::UnsupportedFormat(ref s) => write!(f, "Unsupported mesh format: {}", s),
+            &MeshError::Empty => write!(f, "Mesh has no triangles"),
+        }
+    }
+}
+
+impl From<io::Error> for MeshError {
+    fn from(err: io::Error) -> MeshError {
+        MeshError::Io(err)
+    }
+}
+
+pub type MeshResult<R> = Result<R, MeshError>;